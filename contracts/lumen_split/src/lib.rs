@@ -1,9 +1,16 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, symbol_short as symbol, Address, Env,
-    String, Vec,
+    contract, contractclient, contracterror, contractimpl, contracttype, symbol_short as symbol,
+    token, Address, BytesN, Env, String, Symbol, Vec,
 };
 
+const MAX_TITLE_LEN: u32 = 64;
+const MAX_NOTE_LEN: u32 = 256;
+const MAX_CATEGORY_LEN: u32 = 32;
+const MAX_URI_LEN: u32 = 128;
+// How many missed instances of one recurring expense a single call catches up.
+const MAX_CATCH_UP: u32 = 24;
+
 #[contracterror]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 #[repr(u32)]
@@ -14,6 +21,42 @@ pub enum Error {
     AlreadyMember = 4,
     NotAuthorized = 5,
     UserNotRegistered = 6,
+    OutstandingBalances = 7,
+    PriceUnavailable = 8,
+    StalePrice = 9,
+    SettlementTokenNotSet = 10,
+    InsufficientPot = 11,
+    PaymentNotFound = 12,
+    InvalidCreditor = 13,
+    AssetMismatch = 14,
+    InvalidWeight = 15,
+    InvalidShare = 16,
+    SharesMismatch = 17,
+    InvalidPercentages = 18,
+    ReceiptNotFound = 19,
+    ReceiptFinalized = 20,
+    AlreadyClaimed = 21,
+    NotClaimed = 22,
+    ExpenseNotFound = 23,
+    ExpenseVoided = 24,
+    ExpenseNotEditable = 25,
+    TitleTooLong = 26,
+    NoteTooLong = 27,
+    CategoryTooLong = 28,
+    InvalidDate = 29,
+    RecurringNotFound = 30,
+    InvalidSchedule = 31,
+    PendingExpenseNotFound = 32,
+    AlreadyApproved = 33,
+    PendingExpired = 34,
+    DisputeNotFound = 35,
+    DisputeAlreadyOpen = 36,
+    ReasonTooLong = 37,
+    AlreadyVoted = 38,
+    UriTooLong = 39,
+    PotNotEmpty = 40,
+    UnclaimedOverThreshold = 41,
+    DisputeDismissed = 42,
 }
 
 #[contracttype]
@@ -21,12 +64,24 @@ pub enum Error {
 pub enum DataKey {
     Group(u32),
     Balance(u32, Address),
+    AssetBalance(u32, Address, Address),
     Counter,
     MemberGroups(Address),
     Expenses(u32),
     UserRegistration(Address),
     UserName(Address),
     Activities(u32),
+    Pot(u32),
+    PotContribution(u32, Address),
+    PotSpent(u32, Address),
+    PendingPayments(u32),
+    PaymentCounter(u32),
+    Receipts(u32),
+    RemainderCursor(u32),
+    Recurring(u32),
+    PendingExpenses(u32),
+    PendingExpenseCounter(u32),
+    Disputes(u32),
 }
 
 #[contracttype]
@@ -35,8 +90,23 @@ pub enum ActivityType {
     Expense = 1,
     Settlement = 2,
     MemberAdded = 3,
+    PotDeposit = 4,
+    PotWithdrawal = 5,
+    ReceiptPosted = 6,
+    ItemClaimed = 7,
+    ItemUnclaimed = 8,
+    ReceiptFinalized = 9,
+    ExpenseEdited = 10,
+    ExpenseVoided = 11,
+    DisputeOpened = 12,
+    DisputeResolved = 13,
+    DisputeUpheld = 14,
+    DisputeDismissed = 15,
+    Income = 16,
 }
 
+// For ItemClaimed and ItemUnclaimed, `amount` is how much the actor's share
+// of the receipt changed: positive when claiming, negative when unclaiming.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Activity {
@@ -45,24 +115,269 @@ pub struct Activity {
     pub actor: Address,
     pub recipient: Option<Address>,
     pub amount: i128,
+    pub asset: Option<Address>,
+    pub expense_id: Option<u32>,
+    pub receipt_id: Option<u32>,
+    pub item_index: Option<u32>,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OracleAsset {
+    Stellar(Address),
+    Other(Symbol),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PriceData {
+    pub price: i128,
+    pub timestamp: u64,
+}
+
+// The subset of the SEP-40 price feed interface used for conversions.
+#[contractclient(name = "PriceOracleClient")]
+pub trait PriceOracle {
+    fn lastprice(env: Env, asset: OracleAsset) -> Option<PriceData>;
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleRate {
+    pub asset: Address,
+    pub amount: i128,
+    pub price: i128,
+    pub base_price: i128,
+    pub timestamp: u64,
+}
+
+// An enum rather than Option<OracleRate>: the SDK cannot convert optional
+// custom structs to ScVal.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Conversion {
+    None,
+    Oracle(OracleRate),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LineItem {
+    pub name: String,
+    pub amount: i128,
+    pub participants: Vec<Address>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Receipt {
+    pub items: Vec<LineItem>,
+    pub tax: i128,
+    pub tip: i128,
+}
+
+// Percent shares are basis points and must total 10,000. Itemized splits
+// share each line item equally among its participants, then spread tax and
+// tip in proportion to what each person ordered. Every split other than
+// Exact rounds each share down, line items and tax included, and hands all
+// the leftover units out one each according to the group's RemainderPolicy,
+// starting from the participant recorded in the expense's remainder_start.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Split {
+    Equal(Vec<Address>),
+    Weighted(Vec<(Address, u32)>),
+    Exact(Vec<(Address, i128)>),
+    Percent(Vec<(Address, u32)>),
+    Itemized(Receipt),
+}
+
+// A receipt posted for members to claim from. Item participants are the
+// claimants so far, and `applied` is what has already been charged to them.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClaimableReceipt {
+    pub id: u32,
+    pub payer: Address,
+    pub items: Vec<LineItem>,
+    pub tax: i128,
+    pub tip: i128,
+    pub applied: Vec<(Address, i128)>,
+    pub finalized: bool,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UnclaimedRule {
+    Payer,
+    AllMembers,
+    Claimants,
+}
+
+// Who receives the units left over when a split does not divide evenly.
+// Rotate continues from a per-group cursor so successive expenses hand the
+// extra units to different members; Random picks the start with the ledger
+// PRNG. The start index used is stored on the expense. Under PayerAbsorbs an
+// income receiver gives the extra units to the others instead of keeping them.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RemainderPolicy {
+    FirstListed,
+    PayerAbsorbs,
+    Rotate,
+    Random,
+}
+
+// Income reverses the usual direction: the members in `payers` received the
+// money and owe each participant their share of it.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EntryKind {
+    Expense,
+    Income,
+}
+
+// Descriptive fields entered with an expense. `occurred_at` is when the
+// spending happened and may be earlier than the ledger time it was recorded.
+// The receipt hash and URI can be given up front or attached later.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpenseDetails {
+    pub title: String,
+    pub note: Option<String>,
+    pub category: String,
+    pub occurred_at: u64,
+    pub receipt_hash: Option<BytesN<32>>,
+    pub receipt_uri: Option<String>,
+}
+
+// When a recurring expense falls due: every `interval` seconds from `start`,
+// stopping after `end` or once `count` instances have been posted.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Recurrence {
+    pub interval: u64,
+    pub start: u64,
+    pub end: Option<u64>,
+    pub count: Option<u32>,
+}
+
+// A template that posts a main-ledger expense each time it falls due. Each
+// instance takes the template's details with `occurred_at` set to its due
+// time.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecurringExpense {
+    pub id: u32,
+    pub payer: Address,
+    pub amount: i128,
+    pub split: Split,
+    pub details: ExpenseDetails,
+    pub recurrence: Recurrence,
+    pub next_due: u64,
+    pub posted: u32,
+    pub active: bool,
+}
+
+// The state of an expense before an edit replaced it.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpenseRevision {
+    pub editor: Address,
+    pub payers: Vec<(Address, i128)>,
+    pub amount: i128,
+    pub asset: Option<Address>,
+    pub conversion: Conversion,
+    pub split: Split,
+    pub shares: Vec<(Address, i128)>,
     pub timestamp: u64,
 }
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Expense {
+    pub id: u32,
+    pub kind: EntryKind,
     pub payer: Address,
+    pub payers: Vec<(Address, i128)>,
     pub amount: i128,
+    pub asset: Option<Address>,
+    pub conversion: Conversion,
     pub participants: Vec<Address>,
+    pub split: Split,
+    pub shares: Vec<(Address, i128)>,
+    pub remainder_policy: RemainderPolicy,
+    pub remainder_start: u32,
+    pub from_pot: bool,
+    pub details: ExpenseDetails,
+    pub voided: bool,
+    pub revisions: Vec<ExpenseRevision>,
+    pub timestamp: u64,
+}
+
+// Amended and Voided are resolutions by the payer or the group creator;
+// Upheld and Dismissed are decided by a majority of the participants.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DisputeStatus {
+    Open,
+    Amended,
+    Voided,
+    Upheld,
+    Dismissed,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dispute {
+    pub expense_id: u32,
+    pub opened_by: Address,
+    pub reason: String,
+    pub status: DisputeStatus,
+    pub upheld_by: Vec<Address>,
+    pub dismissed_by: Vec<Address>,
     pub timestamp: u64,
 }
 
+// An expense over the group's approval threshold, waiting on the members it
+// charges. `expires_at` is 0 when the group sets no approval window. An edit
+// that needs approval names the expense it `replaces` and its `editor`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingExpense {
+    pub id: u32,
+    pub expense: Expense,
+    pub approvers: Vec<Address>,
+    pub approvals: Vec<Address>,
+    pub required: u32,
+    pub expires_at: u64,
+    pub replaces: Option<u32>,
+    pub editor: Option<Address>,
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Group {
     pub name: String,
     pub members: Vec<Address>,
     pub creator: Address,
+    pub settlement_token: Option<Address>,
+    pub assets: Vec<Address>,
+    pub price_oracle: Option<Address>,
+    pub max_price_age: u64,
+    pub remainder_policy: RemainderPolicy,
+    pub approval_threshold: i128,
+    pub approval_quorum: u32,
+    pub approval_window: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssetBalance {
+    pub asset: Address,
+    pub balance: i128,
 }
 
 #[contracttype]
@@ -71,6 +386,7 @@ pub struct MemberInfo {
     pub address: Address,
     pub name: String,
     pub balance: i128,
+    pub asset_balances: Vec<AssetBalance>,
 }
 
 #[contracttype]
@@ -79,6 +395,52 @@ pub struct GroupWithBalances {
     pub name: String,
     pub members: Vec<MemberInfo>,
     pub creator: Address,
+    pub settlement_token: Option<Address>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PotMember {
+    pub address: Address,
+    pub contributed: i128,
+    pub spent: i128,
+    pub remaining: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PotSummary {
+    pub total: i128,
+    pub members: Vec<PotMember>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CategoryMember {
+    pub address: Address,
+    pub paid: i128,
+    pub consumed: i128,
+}
+
+// Spending in one category on one ledger; `asset` is None for the main one.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CategorySummary {
+    pub category: String,
+    pub asset: Option<Address>,
+    pub total: i128,
+    pub members: Vec<CategoryMember>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingPayment {
+    pub id: u32,
+    pub from: Address,
+    pub to: Address,
+    pub amount: i128,
+    pub asset: Option<Address>,
+    pub timestamp: u64,
 }
 
 #[contracttype]
@@ -87,6 +449,7 @@ pub struct Settlement {
     pub from: Address,
     pub to: Address,
     pub amount: i128,
+    pub asset: Option<Address>,
 }
 #[contract]
 pub struct LumenSplit;
@@ -125,6 +488,7 @@ impl LumenSplit {
         creator: Address,
         name: String,
         members: Vec<Address>,
+        settlement_token: Option<Address>,
     ) -> Result<u32, Error> {
         creator.require_auth();
 
@@ -154,6 +518,14 @@ impl LumenSplit {
             name: name.clone(),
             members: all_members.clone(),
             creator: creator.clone(),
+            settlement_token,
+            assets: Vec::new(&env),
+            price_oracle: None,
+            max_price_age: 0,
+            remainder_policy: RemainderPolicy::FirstListed,
+            approval_threshold: 0,
+            approval_quorum: 0,
+            approval_window: 0,
         };
 
         env.storage()
@@ -172,7 +544,7 @@ impl LumenSplit {
                 .persistent()
                 .get(&DataKey::MemberGroups(member.clone()))
                 .unwrap_or(Vec::new(&env));
-            if !member_groups.contains(&counter) {
+            if !member_groups.contains(counter) {
                 member_groups.push_back(counter);
                 env.storage()
                     .persistent()
@@ -187,11 +559,12 @@ impl LumenSplit {
             creator.clone(),
             None,
             0,
+            None,
         );
 
         env.events().publish(
             (symbol!("group"), symbol!("created")),
-            (counter, name, creator),
+            (counter, name, creator, group.settlement_token),
         );
 
         Ok(counter)
@@ -204,171 +577,3215 @@ impl LumenSplit {
         amount: i128,
         participants: Vec<Address>,
     ) -> Result<(), Error> {
-        payer.require_auth();
+        let payers = Vec::from_array(&env, [(payer.clone(), amount)]);
+        Self::record_expense(
+            &env,
+            payer,
+            group_id,
+            None,
+            payers,
+            Split::Equal(participants),
+            Self::default_details(&env),
+            false,
+        )
+    }
 
-        if amount <= 0 {
-            return Err(Error::InvalidAmount);
-        }
+    pub fn add_asset_expense(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        asset: Address,
+        amount: i128,
+        participants: Vec<Address>,
+    ) -> Result<(), Error> {
+        let payers = Vec::from_array(&env, [(payer.clone(), amount)]);
+        Self::record_expense(
+            &env,
+            payer,
+            group_id,
+            Some(asset),
+            payers,
+            Split::Equal(participants),
+            Self::default_details(&env),
+            false,
+        )
+    }
 
-        let group: Group = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Group(group_id))
-            .ok_or(Error::GroupNotFound)?;
+    pub fn add_split_expense(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        asset: Option<Address>,
+        amount: i128,
+        split: Split,
+    ) -> Result<(), Error> {
+        let payers = Vec::from_array(&env, [(payer.clone(), amount)]);
+        Self::record_expense(
+            &env,
+            payer,
+            group_id,
+            asset,
+            payers,
+            split,
+            Self::default_details(&env),
+            false,
+        )
+    }
 
-        if !group.members.contains(&payer) {
-            return Err(Error::NotAMember);
-        }
+    pub fn add_multi_payer_expense(
+        env: Env,
+        submitter: Address,
+        group_id: u32,
+        asset: Option<Address>,
+        payers: Vec<(Address, i128)>,
+        split: Split,
+    ) -> Result<(), Error> {
+        Self::record_expense(
+            &env,
+            submitter,
+            group_id,
+            asset,
+            payers,
+            split,
+            Self::default_details(&env),
+            false,
+        )
+    }
 
-        for p in participants.iter() {
-            if !group.members.contains(&p) {
-                return Err(Error::NotAMember);
-            }
-        }
+    pub fn add_detailed_expense(
+        env: Env,
+        submitter: Address,
+        group_id: u32,
+        asset: Option<Address>,
+        payers: Vec<(Address, i128)>,
+        split: Split,
+        details: ExpenseDetails,
+    ) -> Result<(), Error> {
+        Self::record_expense(
+            &env, submitter, group_id, asset, payers, split, details, false,
+        )
+    }
 
-        let num_participants = participants.len() as i128;
-        if num_participants == 0 {
-            return Err(Error::InvalidAmount);
-        }
+    pub fn add_income(
+        env: Env,
+        receiver: Address,
+        group_id: u32,
+        asset: Option<Address>,
+        amount: i128,
+        split: Split,
+        details: ExpenseDetails,
+    ) -> Result<(), Error> {
+        receiver.require_auth();
 
-        let split_amount = amount / num_participants;
-        let remainder = amount % num_participants;
+        Self::validate_details(&env, &details)?;
+        let payers = Vec::from_array(&env, [(receiver.clone(), amount)]);
+        let (_, mut income) = Self::build_expense(
+            &env,
+            group_id,
+            EntryKind::Income,
+            receiver,
+            asset,
+            payers,
+            split,
+        )?;
 
-        for (i, participant) in participants.iter().enumerate() {
-            let current_balance: i128 = env
-                .storage()
-                .persistent()
-                .get(&DataKey::Balance(group_id, participant.clone()))
-                .unwrap_or(0);
+        income.details = details;
+        Self::apply_expense(&env, group_id, &income, 1);
+        Self::commit_expense(&env, group_id, income);
+
+        Ok(())
+    }
+
+    pub fn edit_expense(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        expense_id: u32,
+        payers: Vec<(Address, i128)>,
+        split: Split,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        let (expenses, previous) = Self::load_editable_expense(&env, &actor, group_id, expense_id)?;
 
-            let mut participant_share = split_amount;
-            if (i as i128) < remainder {
-                participant_share += 1;
+        // Anyone newly credited as a payer has to sign for it.
+        for (p, _) in payers.iter() {
+            if p != actor && !previous.payers.iter().any(|(q, _)| q == p) {
+                p.require_auth();
             }
+        }
 
-            if participant == payer {
-                let net_lent = amount - participant_share;
-                env.storage().persistent().set(
-                    &DataKey::Balance(group_id, participant),
-                    &(current_balance + net_lent),
-                );
-            } else {
-                env.storage().persistent().set(
-                    &DataKey::Balance(group_id, participant),
-                    &(current_balance - participant_share),
-                );
+        // Edits are entered in the asset originally spent, so a converted
+        // expense is priced again at the current oracle rate.
+        let asset = match previous.conversion.clone() {
+            Conversion::Oracle(rate) => Some(rate.asset),
+            Conversion::None => previous.asset.clone(),
+        };
+        let (group, expense) = Self::build_expense(
+            &env,
+            group_id,
+            previous.kind.clone(),
+            previous.payer.clone(),
+            asset,
+            payers,
+            split,
+        )?;
+
+        // Payers whose credit shrinks or disappears have to agree to it too,
+        // unless the group creator is making the correction.
+        if actor != group.creator {
+            for (q, paid) in previous.payers.iter() {
+                let mut credited: i128 = 0;
+                for (p, amount) in expense.payers.iter() {
+                    if p == q {
+                        credited += amount;
+                    }
+                }
+                if q != actor && credited < paid {
+                    q.require_auth();
+                }
             }
         }
 
-        let mut expenses: Vec<Expense> = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Expenses(group_id))
-            .unwrap_or(Vec::new(&env));
-        let expense = Expense {
-            payer: payer.clone(),
-            amount,
-            participants: participants.clone(),
+        // An edit that takes the expense over the threshold needs the same
+        // approvals a new expense of that size would.
+        let approvers = Self::required_approvers(&env, &expense);
+        if Self::needs_approval(&group, &expense, &approvers) {
+            Self::hold_expense(
+                &env,
+                group_id,
+                &group,
+                expense,
+                approvers,
+                Some(expense_id),
+                Some(actor),
+            );
+            return Ok(());
+        }
+
+        Self::replace_expense(&env, group_id, expenses, previous, expense, actor);
+
+        Ok(())
+    }
+
+    // Swaps a stored expense for its edited version and records the old
+    // figures as a revision.
+    fn replace_expense(
+        env: &Env,
+        group_id: u32,
+        mut expenses: Vec<Expense>,
+        previous: Expense,
+        mut expense: Expense,
+        actor: Address,
+    ) {
+        let expense_id = previous.id;
+        Self::apply_expense(env, group_id, &previous, -1);
+        Self::apply_expense(env, group_id, &expense, 1);
+
+        expense.id = expense_id;
+        expense.details = previous.details.clone();
+        expense.timestamp = previous.timestamp;
+        expense.revisions = previous.revisions.clone();
+        expense.revisions.push_back(ExpenseRevision {
+            editor: actor.clone(),
+            payers: previous.payers,
+            amount: previous.amount,
+            asset: previous.asset,
+            conversion: previous.conversion,
+            split: previous.split,
+            shares: previous.shares,
             timestamp: env.ledger().timestamp(),
-        };
-        expenses.push_back(expense);
+        });
+        let amount = expense.amount;
+        let asset = expense.asset.clone();
+        expenses.set(expense_id - 1, expense);
         env.storage()
             .persistent()
             .set(&DataKey::Expenses(group_id), &expenses);
 
-        Self::record_activity(
-            &env,
+        Self::record_expense_activity(
+            env,
             group_id,
-            ActivityType::Expense,
-            payer.clone(),
-            None,
+            ActivityType::ExpenseEdited,
+            actor.clone(),
             amount,
+            asset,
+            expense_id,
         );
 
         env.events().publish(
-            (symbol!("expense"), symbol!("added")),
-            (group_id, payer, amount),
+            (symbol!("expense"), symbol!("edited")),
+            (group_id, expense_id, actor.clone(), previous.amount, amount),
         );
 
-        Ok(())
+        Self::close_dispute(env, group_id, expense_id, actor, DisputeStatus::Amended);
     }
 
-    pub fn add_member(
+    pub fn void_expense(
         env: Env,
         actor: Address,
         group_id: u32,
-        new_member: Address,
+        expense_id: u32,
     ) -> Result<(), Error> {
         actor.require_auth();
 
-        if !Self::is_registered(env.clone(), actor.clone()) {
-            return Err(Error::UserNotRegistered);
-        }
+        let (expenses, expense) = Self::load_editable_expense(&env, &actor, group_id, expense_id)?;
 
-        if !Self::is_registered(env.clone(), new_member.clone()) {
-            return Err(Error::UserNotRegistered);
-        }
+        Self::discard_expense(&env, group_id, expenses, expense, actor.clone());
+        Self::close_dispute(&env, group_id, expense_id, actor, DisputeStatus::Voided);
 
-        let mut group: Group = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Group(group_id))
-            .ok_or(Error::GroupNotFound)?;
+        Ok(())
+    }
 
-        if !group.members.contains(&actor) {
-            return Err(Error::NotAMember);
+    // The receipt itself stays off-chain; the hash lets anyone check that a
+    // file fetched from `uri` is the one the payer attached.
+    pub fn attach_receipt(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        expense_id: u32,
+        hash: BytesN<32>,
+        uri: String,
+    ) -> Result<(), Error> {
+        payer.require_auth();
+
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
         }
 
-        if group.members.contains(&new_member) {
-            return Err(Error::AlreadyMember);
+        let mut expenses = Self::get_expenses(env.clone(), group_id);
+        let mut expense = expenses
+            .get(expense_id.wrapping_sub(1))
+            .ok_or(Error::ExpenseNotFound)?;
+
+        if payer != expense.payer && !expense.payers.iter().any(|(p, _)| p == payer) {
+            return Err(Error::NotAuthorized);
+        }
+        if expense.voided {
+            return Err(Error::ExpenseVoided);
+        }
+        if uri.len() > MAX_URI_LEN {
+            return Err(Error::UriTooLong);
         }
 
-        group.members.push_back(new_member.clone());
+        expense.details.receipt_hash = Some(hash.clone());
+        expense.details.receipt_uri = Some(uri.clone());
+        expenses.set(expense_id - 1, expense);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Expenses(group_id), &expenses);
+
+        env.events().publish(
+            (symbol!("expense"), symbol!("receipt")),
+            (group_id, expense_id, payer, hash, uri),
+        );
+
+        Ok(())
+    }
+
+    pub fn verify_receipt(
+        env: Env,
+        group_id: u32,
+        expense_id: u32,
+        hash: BytesN<32>,
+    ) -> Result<bool, Error> {
+        let expense = Self::get_expense(env, group_id, expense_id)?;
+        Ok(expense.details.receipt_hash == Some(hash))
+    }
+
+    pub fn open_dispute(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        expense_id: u32,
+        reason: String,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
+        }
+
+        let expense = Self::get_expense(env.clone(), group_id, expense_id)?;
+        if !expense.participants.contains(&member) {
+            return Err(Error::NotAuthorized);
+        }
+        if expense.voided {
+            return Err(Error::ExpenseVoided);
+        }
+        if expense.from_pot {
+            return Err(Error::ExpenseNotEditable);
+        }
+        if reason.len() > MAX_NOTE_LEN {
+            return Err(Error::ReasonTooLong);
+        }
+
+        let mut disputes = Self::get_disputes(env.clone(), group_id);
+        if disputes
+            .iter()
+            .any(|d| d.expense_id == expense_id && d.status == DisputeStatus::Open)
+        {
+            return Err(Error::DisputeAlreadyOpen);
+        }
+        // Once the group has dismissed a dispute the expense stands; it
+        // cannot be frozen again by reopening it.
+        if disputes
+            .iter()
+            .any(|d| d.expense_id == expense_id && d.status == DisputeStatus::Dismissed)
+        {
+            return Err(Error::DisputeDismissed);
+        }
+
+        // Opening a dispute is not a vote; the opener votes like everyone else.
+        disputes.push_back(Dispute {
+            expense_id,
+            opened_by: member.clone(),
+            reason: reason.clone(),
+            status: DisputeStatus::Open,
+            upheld_by: Vec::new(&env),
+            dismissed_by: Vec::new(&env),
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage()
+            .persistent()
+            .set(&DataKey::Disputes(group_id), &disputes);
+
+        Self::record_expense_activity(
+            &env,
+            group_id,
+            ActivityType::DisputeOpened,
+            member.clone(),
+            expense.amount,
+            expense.asset.clone(),
+            expense_id,
+        );
+
+        env.events().publish(
+            (symbol!("dispute"), symbol!("opened")),
+            (group_id, expense_id, member, reason),
+        );
+
+        Ok(())
+    }
+
+    pub fn vote_dispute(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        expense_id: u32,
+        uphold: bool,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
+        }
+
+        let mut disputes = Self::get_disputes(env.clone(), group_id);
+        let index = disputes
+            .iter()
+            .position(|d| d.expense_id == expense_id && d.status == DisputeStatus::Open)
+            .ok_or(Error::DisputeNotFound)? as u32;
+        let mut dispute = disputes.get(index).unwrap();
+
+        let expense = Self::get_expense(env.clone(), group_id, expense_id)?;
+        if !Self::dispute_electorate(&expense).contains(&member) {
+            return Err(Error::NotAuthorized);
+        }
+        if dispute.upheld_by.contains(&member) || dispute.dismissed_by.contains(&member) {
+            return Err(Error::AlreadyVoted);
+        }
+
+        if uphold {
+            dispute.upheld_by.push_back(member.clone());
+        } else {
+            dispute.dismissed_by.push_back(member.clone());
+        }
+        disputes.set(index, dispute);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Disputes(group_id), &disputes);
+
+        env.events().publish(
+            (symbol!("dispute"), symbol!("voted")),
+            (group_id, expense_id, member.clone(), uphold),
+        );
+
+        Self::tally_dispute(&env, group_id, index, member);
+
+        Ok(())
+    }
+
+    pub fn get_disputes(env: Env, group_id: u32) -> Vec<Dispute> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Disputes(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn add_pot_expense(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        amount: i128,
+        participants: Vec<Address>,
+    ) -> Result<(), Error> {
+        let payers = Vec::from_array(&env, [(payer.clone(), amount)]);
+        Self::record_expense(
+            &env,
+            payer,
+            group_id,
+            None,
+            payers,
+            Split::Equal(participants),
+            Self::default_details(&env),
+            true,
+        )
+    }
+
+    pub fn add_recurring_expense(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        amount: i128,
+        split: Split,
+        details: ExpenseDetails,
+        recurrence: Recurrence,
+    ) -> Result<u32, Error> {
+        payer.require_auth();
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if !group.members.contains(&payer) {
+            return Err(Error::NotAMember);
+        }
+
+        let participants = Self::split_participants(&env, &split);
+        for p in participants.iter() {
+            if !group.members.contains(&p) {
+                return Err(Error::NotAMember);
+            }
+        }
+
+        if amount <= 0 || participants.is_empty() {
+            return Err(Error::InvalidAmount);
+        }
+
+        Self::validate_split(&split, amount)?;
+        Self::validate_details(&env, &details)?;
+
+        if recurrence.interval == 0
+            || recurrence.count == Some(0)
+            || recurrence.end.is_some_and(|end| end < recurrence.start)
+        {
+            return Err(Error::InvalidSchedule);
+        }
+
+        let mut templates = Self::get_recurring_expenses(env.clone(), group_id);
+        let id = templates.len() + 1;
+        templates.push_back(RecurringExpense {
+            id,
+            payer: payer.clone(),
+            amount,
+            split,
+            details,
+            recurrence: recurrence.clone(),
+            next_due: recurrence.start,
+            posted: 0,
+            active: true,
+        });
+        env.storage()
+            .persistent()
+            .set(&DataKey::Recurring(group_id), &templates);
+
+        env.events().publish(
+            (symbol!("recurring"), symbol!("added")),
+            (group_id, id, payer, amount, recurrence),
+        );
+
+        Ok(id)
+    }
+
+    // Anyone may call this: the payer signed for every instance when the
+    // template was registered.
+    pub fn process_due(env: Env, group_id: u32) -> Result<u32, Error> {
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
+        }
+
+        let now = env.ledger().timestamp();
+        let mut templates = Self::get_recurring_expenses(env.clone(), group_id);
+        let mut posted: u32 = 0;
+        for i in 0..templates.len() {
+            let mut template = templates.get(i).unwrap();
+            let mut caught_up: u32 = 0;
+            while template.active && template.next_due <= now && caught_up < MAX_CATCH_UP {
+                let mut details = template.details.clone();
+                details.occurred_at = template.next_due;
+                Self::post_expense(
+                    &env,
+                    template.payer.clone(),
+                    group_id,
+                    None,
+                    Vec::from_array(&env, [(template.payer.clone(), template.amount)]),
+                    template.split.clone(),
+                    details,
+                    false,
+                )?;
+
+                template.posted += 1;
+                template.next_due += template.recurrence.interval;
+                caught_up += 1;
+
+                let finished = template
+                    .recurrence
+                    .count
+                    .is_some_and(|count| template.posted >= count)
+                    || template
+                        .recurrence
+                        .end
+                        .is_some_and(|end| template.next_due > end);
+                if finished {
+                    template.active = false;
+                }
+            }
+            posted += caught_up;
+            templates.set(i, template);
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Recurring(group_id), &templates);
+
+        Ok(posted)
+    }
+
+    pub fn cancel_recurring_expense(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        recurring_id: u32,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        let mut templates = Self::get_recurring_expenses(env.clone(), group_id);
+        let index = recurring_id.wrapping_sub(1);
+        let mut template = templates.get(index).ok_or(Error::RecurringNotFound)?;
+
+        if actor != template.payer && actor != group.creator {
+            return Err(Error::NotAuthorized);
+        }
+
+        template.active = false;
+        templates.set(index, template);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Recurring(group_id), &templates);
+
+        env.events().publish(
+            (symbol!("recurring"), symbol!("cancelled")),
+            (group_id, recurring_id, actor),
+        );
+
+        Ok(())
+    }
+
+    pub fn get_recurring_expenses(env: Env, group_id: u32) -> Vec<RecurringExpense> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Recurring(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn set_approval_policy(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        threshold: i128,
+        quorum: u32,
+        window: u64,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        let mut group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if actor != group.creator {
+            return Err(Error::NotAuthorized);
+        }
+
+        if threshold < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        group.approval_threshold = threshold;
+        group.approval_quorum = quorum;
+        group.approval_window = window;
         env.storage()
             .persistent()
             .set(&DataKey::Group(group_id), &group);
+
+        env.events().publish(
+            (symbol!("group"), symbol!("approval")),
+            (group_id, threshold, quorum, window),
+        );
+
+        Ok(())
+    }
+
+    pub fn approve_expense(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        pending_id: u32,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        let (mut pending, index, mut entry) =
+            Self::load_pending_expense(&env, &member, group_id, pending_id)?;
+
+        if entry.approvals.contains(&member) {
+            return Err(Error::AlreadyApproved);
+        }
+        entry.approvals.push_back(member.clone());
+
+        env.events().publish(
+            (symbol!("expense"), symbol!("approved")),
+            (group_id, pending_id, member),
+        );
+
+        if entry.approvals.len() < entry.required {
+            pending.set(index, entry);
+        } else if let (Some(expense_id), Some(editor)) = (entry.replaces, entry.editor) {
+            // The expense may have been edited again since; the approved
+            // version replaces whatever is stored now.
+            let expenses = Self::get_expenses(env.clone(), group_id);
+            let previous = expenses.get(expense_id - 1).ok_or(Error::ExpenseNotFound)?;
+            if previous.voided {
+                return Err(Error::ExpenseVoided);
+            }
+            pending.remove(index);
+            Self::replace_expense(&env, group_id, expenses, previous, entry.expense, editor);
+        } else {
+            pending.remove(index);
+            if entry.expense.from_pot {
+                let group: Group = env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::Group(group_id))
+                    .ok_or(Error::GroupNotFound)?;
+                Self::pay_from_pot(&env, group_id, &group, &entry.expense)?;
+            } else {
+                Self::apply_expense(&env, group_id, &entry.expense, 1);
+            }
+            Self::commit_expense(&env, group_id, entry.expense);
+        }
         env.storage()
             .persistent()
-            .set(&DataKey::Balance(group_id, new_member.clone()), &0i128);
+            .set(&DataKey::PendingExpenses(group_id), &pending);
+
+        Ok(())
+    }
+
+    pub fn reject_expense(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        pending_id: u32,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        let (mut pending, index, _) =
+            Self::load_pending_expense(&env, &member, group_id, pending_id)?;
+
+        pending.remove(index);
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingExpenses(group_id), &pending);
+
+        env.events().publish(
+            (symbol!("expense"), symbol!("rejected")),
+            (group_id, pending_id, member),
+        );
+
+        Ok(())
+    }
+
+    // Anyone may clear out pending expenses whose approval window has passed.
+    pub fn expire_pending_expenses(env: Env, group_id: u32) -> Result<u32, Error> {
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
+        }
+
+        let now = env.ledger().timestamp();
+        let mut kept = Vec::new(&env);
+        let mut expired: u32 = 0;
+        for entry in Self::get_pending_expenses(env.clone(), group_id).iter() {
+            if entry.expires_at != 0 && now > entry.expires_at {
+                env.events().publish(
+                    (symbol!("expense"), symbol!("expired")),
+                    (group_id, entry.id),
+                );
+                expired += 1;
+            } else {
+                kept.push_back(entry);
+            }
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingExpenses(group_id), &kept);
+
+        Ok(expired)
+    }
+
+    pub fn get_pending_expenses(env: Env, group_id: u32) -> Vec<PendingExpense> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PendingExpenses(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn add_member(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        new_member: Address,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        if !Self::is_registered(env.clone(), actor.clone()) {
+            return Err(Error::UserNotRegistered);
+        }
+
+        if !Self::is_registered(env.clone(), new_member.clone()) {
+            return Err(Error::UserNotRegistered);
+        }
+
+        let mut group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if !group.members.contains(&actor) {
+            return Err(Error::NotAMember);
+        }
+
+        if group.members.contains(&new_member) {
+            return Err(Error::AlreadyMember);
+        }
+
+        group.members.push_back(new_member.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::Group(group_id), &group);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Balance(group_id, new_member.clone()), &0i128);
+
+        let mut member_groups: Vec<u32> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::MemberGroups(new_member.clone()))
+            .unwrap_or(Vec::new(&env));
+        if !member_groups.contains(group_id) {
+            member_groups.push_back(group_id);
+            env.storage()
+                .persistent()
+                .set(&DataKey::MemberGroups(new_member.clone()), &member_groups);
+        }
+
+        env.events().publish(
+            (symbol!("group"), symbol!("mem_add")),
+            (group_id, new_member.clone(), actor.clone()),
+        );
+
+        Self::record_activity(
+            &env,
+            group_id,
+            ActivityType::MemberAdded,
+            actor,
+            Some(new_member),
+            0,
+            None,
+        );
+
+        Ok(())
+    }
+
+    pub fn settle_debt(
+        env: Env,
+        from: Address,
+        group_id: u32,
+        to: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        Self::settle(&env, from, group_id, to, None, amount)
+    }
+
+    pub fn settle_asset_debt(
+        env: Env,
+        from: Address,
+        group_id: u32,
+        to: Address,
+        asset: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        Self::settle(&env, from, group_id, to, Some(asset), amount)
+    }
+
+    pub fn set_settlement_token(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        settlement_token: Address,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        let mut group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if actor != group.creator {
+            return Err(Error::NotAuthorized);
+        }
+
+        // Balances are denominated in the settlement token, so switching it
+        // while debts are open would silently change what they are worth.
+        if Self::has_open_balances(&env, group_id, &group) {
+            return Err(Error::OutstandingBalances);
+        }
+
+        // The pot does not record which token it was funded in; every group's
+        // pot is held at this contract, so a switch would let the group pay
+        // out another group's tokens.
+        let pot: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Pot(group_id))
+            .unwrap_or(0);
+        if pot != 0 {
+            return Err(Error::PotNotEmpty);
+        }
+
+        group.settlement_token = Some(settlement_token.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::Group(group_id), &group);
+
+        env.events().publish(
+            (symbol!("group"), symbol!("token")),
+            (group_id, settlement_token),
+        );
+
+        Ok(())
+    }
+
+    pub fn set_price_oracle(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        price_oracle: Option<Address>,
+        max_price_age: u64,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        let mut group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if actor != group.creator {
+            return Err(Error::NotAuthorized);
+        }
+
+        if price_oracle.is_some() && group.settlement_token.is_none() {
+            return Err(Error::SettlementTokenNotSet);
+        }
+
+        group.price_oracle = price_oracle.clone();
+        group.max_price_age = max_price_age;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Group(group_id), &group);
+
+        env.events().publish(
+            (symbol!("group"), symbol!("oracle")),
+            (group_id, price_oracle, max_price_age),
+        );
+
+        Ok(())
+    }
+
+    pub fn set_remainder_policy(
+        env: Env,
+        actor: Address,
+        group_id: u32,
+        policy: RemainderPolicy,
+    ) -> Result<(), Error> {
+        actor.require_auth();
+
+        let mut group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if actor != group.creator {
+            return Err(Error::NotAuthorized);
+        }
+
+        group.remainder_policy = policy.clone();
+        env.storage()
+            .persistent()
+            .set(&DataKey::Group(group_id), &group);
+
+        env.events()
+            .publish((symbol!("group"), symbol!("remainder")), (group_id, policy));
+
+        Ok(())
+    }
+
+    pub fn deposit_to_pot(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        amount: i128,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if !group.members.contains(&member) {
+            return Err(Error::NotAMember);
+        }
+
+        let settlement_token = group.settlement_token.ok_or(Error::SettlementTokenNotSet)?;
+        token::Client::new(&env, &settlement_token).transfer(
+            &member,
+            &env.current_contract_address(),
+            &amount,
+        );
+
+        let pot: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Pot(group_id))
+            .unwrap_or(0);
+        let contributed: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::PotContribution(group_id, member.clone()))
+            .unwrap_or(0);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Pot(group_id), &(pot + amount));
+        env.storage().persistent().set(
+            &DataKey::PotContribution(group_id, member.clone()),
+            &(contributed + amount),
+        );
+
+        env.events().publish(
+            (symbol!("pot"), symbol!("deposit")),
+            (group_id, member.clone(), amount),
+        );
+
+        Self::record_activity(
+            &env,
+            group_id,
+            ActivityType::PotDeposit,
+            member,
+            None,
+            amount,
+            None,
+        );
+
+        Ok(())
+    }
+
+    pub fn withdraw_from_pot(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        amount: i128,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if !group.members.contains(&member) {
+            return Err(Error::NotAMember);
+        }
+
+        let settlement_token = group.settlement_token.ok_or(Error::SettlementTokenNotSet)?;
+
+        let pot: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Pot(group_id))
+            .unwrap_or(0);
+        let contributed: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::PotContribution(group_id, member.clone()))
+            .unwrap_or(0);
+        let spent: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::PotSpent(group_id, member.clone()))
+            .unwrap_or(0);
+
+        // Members may only take back what they put in and have not consumed.
+        if amount > contributed - spent || amount > pot {
+            return Err(Error::InsufficientPot);
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Pot(group_id), &(pot - amount));
+        env.storage().persistent().set(
+            &DataKey::PotContribution(group_id, member.clone()),
+            &(contributed - amount),
+        );
+        token::Client::new(&env, &settlement_token).transfer(
+            &env.current_contract_address(),
+            &member,
+            &amount,
+        );
+
+        env.events().publish(
+            (symbol!("pot"), symbol!("withdraw")),
+            (group_id, member.clone(), amount),
+        );
+
+        Self::record_activity(
+            &env,
+            group_id,
+            ActivityType::PotWithdrawal,
+            member,
+            None,
+            amount,
+            None,
+        );
+
+        Ok(())
+    }
+
+    pub fn get_pot(env: Env, group_id: u32) -> Result<PotSummary, Error> {
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        let mut members = Vec::new(&env);
+        for member in group.members.iter() {
+            let contributed: i128 = env
+                .storage()
+                .persistent()
+                .get(&DataKey::PotContribution(group_id, member.clone()))
+                .unwrap_or(0);
+            let spent: i128 = env
+                .storage()
+                .persistent()
+                .get(&DataKey::PotSpent(group_id, member.clone()))
+                .unwrap_or(0);
+            members.push_back(PotMember {
+                address: member,
+                contributed,
+                spent,
+                remaining: contributed - spent,
+            });
+        }
+
+        Ok(PotSummary {
+            total: env
+                .storage()
+                .persistent()
+                .get(&DataKey::Pot(group_id))
+                .unwrap_or(0),
+            members,
+        })
+    }
+
+    pub fn get_category_summary(
+        env: Env,
+        group_id: u32,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<Vec<CategorySummary>, Error> {
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
+        }
+
+        // Totals come from the shares stored on each expense, so they match
+        // what was applied to the balances. Expenses are placed in the range
+        // by the date they occurred.
+        let mut summaries: Vec<CategorySummary> = Vec::new(&env);
+        for expense in Self::get_expenses(env.clone(), group_id).iter() {
+            let occurred_at = expense.details.occurred_at;
+            if expense.voided
+                || expense.kind == EntryKind::Income
+                || from.is_some_and(|from| occurred_at < from)
+                || to.is_some_and(|to| occurred_at > to)
+            {
+                continue;
+            }
+
+            let index = match summaries.iter().position(|summary| {
+                summary.category == expense.details.category && summary.asset == expense.asset
+            }) {
+                Some(i) => i as u32,
+                None => {
+                    summaries.push_back(CategorySummary {
+                        category: expense.details.category.clone(),
+                        asset: expense.asset.clone(),
+                        total: 0,
+                        members: Vec::new(&env),
+                    });
+                    summaries.len() - 1
+                }
+            };
+
+            let mut summary = summaries.get(index).unwrap();
+            summary.total += expense.amount;
+            for (payer, paid) in expense.payers.iter() {
+                Self::add_category_member(&mut summary.members, payer, paid, 0);
+            }
+            for (participant, share) in expense.shares.iter() {
+                Self::add_category_member(&mut summary.members, participant, 0, share);
+            }
+            summaries.set(index, summary);
+        }
+
+        Ok(summaries)
+    }
+
+    pub fn get_balance(env: Env, group_id: u32, member: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Balance(group_id, member))
+            .unwrap_or(0)
+    }
+
+    pub fn get_asset_balance(env: Env, group_id: u32, member: Address, asset: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AssetBalance(group_id, member, asset))
+            .unwrap_or(0)
+    }
+
+    pub fn get_group(env: Env, group_id: u32) -> Result<Group, Error> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)
+    }
+
+    pub fn get_group_count(env: Env) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Counter)
+            .unwrap_or(0)
+    }
+
+    pub fn get_group_with_balances(env: Env, group_id: u32) -> Result<GroupWithBalances, Error> {
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        let mut members = Vec::new(&env);
+        for member_addr in group.members.iter() {
+            let balance: i128 = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Balance(group_id, member_addr.clone()))
+                .unwrap_or(0);
+
+            let mut asset_balances = Vec::new(&env);
+            for asset in group.assets.iter() {
+                let asset_balance: i128 = env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::AssetBalance(
+                        group_id,
+                        member_addr.clone(),
+                        asset.clone(),
+                    ))
+                    .unwrap_or(0);
+                asset_balances.push_back(AssetBalance {
+                    asset,
+                    balance: asset_balance,
+                });
+            }
+
+            let name = Self::get_user_name(env.clone(), member_addr.clone());
+
+            members.push_back(MemberInfo {
+                address: member_addr,
+                name,
+                balance,
+                asset_balances,
+            });
+        }
+
+        Ok(GroupWithBalances {
+            name: group.name,
+            members,
+            creator: group.creator,
+            settlement_token: group.settlement_token,
+        })
+    }
+
+    pub fn get_settlements(env: Env, group_id: u32) -> Result<Vec<Settlement>, Error> {
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        // Expenses under an open dispute stay out of the plan until the
+        // dispute is resolved.
+        let mut frozen: Vec<Expense> = Vec::new(&env);
+        for dispute in Self::get_disputes(env.clone(), group_id).iter() {
+            if dispute.status == DisputeStatus::Open {
+                frozen.push_back(Self::get_expense(
+                    env.clone(),
+                    group_id,
+                    dispute.expense_id,
+                )?);
+            }
+        }
+
+        // Each asset is its own ledger; debts are never netted across assets.
+        let mut settlements: Vec<Settlement> = Vec::new(&env);
+        Self::plan_settlements(&env, group_id, &group, None, &frozen, &mut settlements);
+        for asset in group.assets.iter() {
+            Self::plan_settlements(
+                &env,
+                group_id,
+                &group,
+                Some(asset),
+                &frozen,
+                &mut settlements,
+            );
+        }
+
+        Ok(settlements)
+    }
+
+    pub fn settle_all(env: Env, group_id: u32) -> Result<Vec<Settlement>, Error> {
+        let settlements = Self::get_settlements(env.clone(), group_id)?;
+        if settlements.is_empty() {
+            return Ok(settlements);
+        }
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        // Every debtor signs; creditors also sign for legs that move no
+        // tokens, as with settle_debt.
+        let mut signers: Vec<Address> = Vec::new(&env);
+        for settlement in settlements.iter() {
+            if !signers.contains(&settlement.from) {
+                settlement.from.require_auth();
+                signers.push_back(settlement.from.clone());
+            }
+            if Self::transfer_token(&group, &settlement.asset).is_none()
+                && !signers.contains(&settlement.to)
+            {
+                settlement.to.require_auth();
+                signers.push_back(settlement.to);
+            }
+        }
+
+        for settlement in settlements.iter() {
+            let token_id = Self::transfer_token(&group, &settlement.asset);
+            Self::execute_settlement(
+                &env,
+                group_id,
+                settlement.from,
+                settlement.to,
+                settlement.asset,
+                settlement.amount,
+                token_id,
+            );
+        }
+
+        env.events().publish(
+            (symbol!("debt"), symbol!("settleall")),
+            (group_id, settlements.clone()),
+        );
+
+        Ok(settlements)
+    }
+
+    pub fn record_payment(
+        env: Env,
+        from: Address,
+        group_id: u32,
+        to: Address,
+        asset: Option<Address>,
+        amount: i128,
+    ) -> Result<u32, Error> {
+        from.require_auth();
+
+        let (_, asset) = Self::check_settlement(&env, &from, group_id, &to, asset, amount)?;
+
+        let mut counter: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::PaymentCounter(group_id))
+            .unwrap_or(0);
+        counter += 1;
+
+        let mut pending = Self::get_pending_payments(env.clone(), group_id);
+        pending.push_back(PendingPayment {
+            id: counter,
+            from: from.clone(),
+            to: to.clone(),
+            amount,
+            asset,
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingPayments(group_id), &pending);
+        env.storage()
+            .persistent()
+            .set(&DataKey::PaymentCounter(group_id), &counter);
+
+        env.events().publish(
+            (symbol!("payment"), symbol!("claimed")),
+            (group_id, counter, from, to, amount),
+        );
+
+        Ok(counter)
+    }
+
+    pub fn confirm_payment(
+        env: Env,
+        to: Address,
+        group_id: u32,
+        payment_id: u32,
+    ) -> Result<(), Error> {
+        to.require_auth();
+
+        let payment = Self::take_pending_payment(&env, &to, group_id, payment_id)?;
+
+        // Balances may have moved since the claim was recorded.
+        let (_, asset) = Self::check_settlement(
+            &env,
+            &payment.from,
+            group_id,
+            &to,
+            payment.asset,
+            payment.amount,
+        )?;
+        Self::execute_settlement(
+            &env,
+            group_id,
+            payment.from.clone(),
+            to.clone(),
+            asset,
+            payment.amount,
+            None,
+        );
+
+        env.events().publish(
+            (symbol!("payment"), symbol!("confirmed")),
+            (group_id, payment_id, payment.from, to, payment.amount),
+        );
+
+        Ok(())
+    }
+
+    pub fn reject_payment(
+        env: Env,
+        to: Address,
+        group_id: u32,
+        payment_id: u32,
+    ) -> Result<(), Error> {
+        to.require_auth();
+
+        let payment = Self::take_pending_payment(&env, &to, group_id, payment_id)?;
+
+        env.events().publish(
+            (symbol!("payment"), symbol!("rejected")),
+            (group_id, payment_id, payment.from, to, payment.amount),
+        );
+
+        Ok(())
+    }
+
+    pub fn record_received_payment(
+        env: Env,
+        to: Address,
+        group_id: u32,
+        from: Address,
+        asset: Option<Address>,
+        amount: i128,
+    ) -> Result<(), Error> {
+        to.require_auth();
+
+        let (_, asset) = Self::check_settlement(&env, &from, group_id, &to, asset, amount)?;
+        Self::execute_settlement(
+            &env,
+            group_id,
+            from.clone(),
+            to.clone(),
+            asset,
+            amount,
+            None,
+        );
+
+        env.events().publish(
+            (symbol!("payment"), symbol!("received")),
+            (group_id, from, to, amount),
+        );
+
+        Ok(())
+    }
+
+    pub fn get_pending_payments(env: Env, group_id: u32) -> Vec<PendingPayment> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PendingPayments(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn get_net_position(env: Env, member: Address, other: Address) -> i128 {
+        let mut net: i128 = 0;
+        for group_id in Self::get_groups_for_member(env.clone(), member.clone()).iter() {
+            net += Self::pair_position(&env, group_id, &member, &other);
+        }
+        net
+    }
+
+    pub fn settle_net(env: Env, from: Address, to: Address) -> Result<i128, Error> {
+        from.require_auth();
+        to.require_auth();
+
+        // Offsets move credit between groups for both parties, so they must
+        // all be in the same unit as the single net transfer.
+        let mut positions: Vec<(u32, i128)> = Vec::new(&env);
+        let mut token_id: Option<Address> = None;
+        let mut net: i128 = 0;
+        for group_id in Self::get_groups_for_member(env.clone(), from.clone()).iter() {
+            let position = Self::pair_position(&env, group_id, &to, &from);
+            if position == 0 {
+                continue;
+            }
+            let group: Group = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Group(group_id))
+                .ok_or(Error::GroupNotFound)?;
+            if positions.is_empty() {
+                token_id = group.settlement_token;
+            } else if token_id != group.settlement_token {
+                return Err(Error::AssetMismatch);
+            }
+            positions.push_back((group_id, position));
+            net += position;
+        }
+
+        if positions.is_empty() || net < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        for (group_id, position) in positions.iter() {
+            if position > 0 {
+                Self::execute_settlement(
+                    &env,
+                    group_id,
+                    from.clone(),
+                    to.clone(),
+                    None,
+                    position,
+                    None,
+                );
+            } else {
+                Self::execute_settlement(
+                    &env,
+                    group_id,
+                    to.clone(),
+                    from.clone(),
+                    None,
+                    -position,
+                    None,
+                );
+            }
+        }
+
+        if let Some(token_id) = token_id {
+            if net > 0 {
+                token::Client::new(&env, &token_id).transfer(&from, &to, &net);
+            }
+        }
+
+        env.events().publish(
+            (symbol!("debt"), symbol!("netted")),
+            (from, to, net, positions),
+        );
+
+        Ok(net)
+    }
+
+    pub fn get_groups_for_member(env: Env, member: Address) -> Vec<u32> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MemberGroups(member))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn post_receipt(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        items: Vec<(String, i128)>,
+        tax: i128,
+        tip: i128,
+    ) -> Result<u32, Error> {
+        payer.require_auth();
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if !group.members.contains(&payer) {
+            return Err(Error::NotAMember);
+        }
+
+        if items.is_empty() || tax < 0 || tip < 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut line_items = Vec::new(&env);
+        let mut total = tax + tip;
+        for (name, amount) in items.iter() {
+            if amount <= 0 {
+                return Err(Error::InvalidAmount);
+            }
+            total += amount;
+            line_items.push_back(LineItem {
+                name,
+                amount,
+                participants: Vec::new(&env),
+            });
+        }
+
+        let mut receipts = Self::get_receipts(env.clone(), group_id);
+        let id = receipts.len() + 1;
+        receipts.push_back(ClaimableReceipt {
+            id,
+            payer: payer.clone(),
+            items: line_items,
+            tax,
+            tip,
+            applied: Vec::new(&env),
+            finalized: false,
+            timestamp: env.ledger().timestamp(),
+        });
+        env.storage()
+            .persistent()
+            .set(&DataKey::Receipts(group_id), &receipts);
+
+        Self::record_receipt_activity(
+            &env,
+            group_id,
+            ActivityType::ReceiptPosted,
+            payer.clone(),
+            total,
+            id,
+            None,
+        );
+
+        env.events().publish(
+            (symbol!("receipt"), symbol!("posted")),
+            (group_id, id, payer, total),
+        );
+
+        Ok(id)
+    }
+
+    pub fn claim_item(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        receipt_id: u32,
+        item_index: u32,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        let (mut receipt, mut item) =
+            Self::load_receipt_item(&env, &member, group_id, receipt_id, item_index)?;
+
+        if item.participants.contains(&member) {
+            return Err(Error::AlreadyClaimed);
+        }
+
+        item.participants.push_back(member.clone());
+        receipt.items.set(item_index, item);
+        let before = Self::applied_share(&receipt, &member);
+        Self::apply_receipt(&env, group_id, &mut receipt, &RemainderPolicy::FirstListed);
+        let change = Self::applied_share(&receipt, &member) - before;
+        Self::save_receipt(&env, group_id, receipt);
+
+        Self::record_receipt_activity(
+            &env,
+            group_id,
+            ActivityType::ItemClaimed,
+            member.clone(),
+            change,
+            receipt_id,
+            Some(item_index),
+        );
+
+        env.events().publish(
+            (symbol!("receipt"), symbol!("claimed")),
+            (group_id, receipt_id, item_index, member),
+        );
+
+        Ok(())
+    }
+
+    pub fn unclaim_item(
+        env: Env,
+        member: Address,
+        group_id: u32,
+        receipt_id: u32,
+        item_index: u32,
+    ) -> Result<(), Error> {
+        member.require_auth();
+
+        let (mut receipt, mut item) =
+            Self::load_receipt_item(&env, &member, group_id, receipt_id, item_index)?;
+
+        let index = item
+            .participants
+            .first_index_of(&member)
+            .ok_or(Error::NotClaimed)?;
+
+        item.participants.remove(index);
+        receipt.items.set(item_index, item);
+        let before = Self::applied_share(&receipt, &member);
+        Self::apply_receipt(&env, group_id, &mut receipt, &RemainderPolicy::FirstListed);
+        let change = Self::applied_share(&receipt, &member) - before;
+        Self::save_receipt(&env, group_id, receipt);
+
+        Self::record_receipt_activity(
+            &env,
+            group_id,
+            ActivityType::ItemUnclaimed,
+            member.clone(),
+            change,
+            receipt_id,
+            Some(item_index),
+        );
+
+        env.events().publish(
+            (symbol!("receipt"), symbol!("unclaimed")),
+            (group_id, receipt_id, item_index, member),
+        );
+
+        Ok(())
+    }
+
+    pub fn finalize_receipt(
+        env: Env,
+        payer: Address,
+        group_id: u32,
+        receipt_id: u32,
+        rule: UnclaimedRule,
+    ) -> Result<(), Error> {
+        payer.require_auth();
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        let mut receipt = Self::get_receipts(env.clone(), group_id)
+            .get(receipt_id.wrapping_sub(1))
+            .ok_or(Error::ReceiptNotFound)?;
+
+        if receipt.payer != payer {
+            return Err(Error::NotAuthorized);
+        }
+
+        if receipt.finalized {
+            return Err(Error::ReceiptFinalized);
+        }
+
+        let mut claimants: Vec<Address> = Vec::new(&env);
+        for item in receipt.items.iter() {
+            for participant in item.participants.iter() {
+                if !claimants.contains(&participant) {
+                    claimants.push_back(participant);
+                }
+            }
+        }
+        let assignees = match rule {
+            UnclaimedRule::AllMembers => group.members.clone(),
+            UnclaimedRule::Claimants if !claimants.is_empty() => claimants,
+            _ => Vec::from_array(&env, [payer.clone()]),
+        };
+
+        for (i, item) in receipt.items.iter().enumerate() {
+            if item.participants.is_empty() {
+                let mut assigned = item.clone();
+                assigned.participants = assignees.clone();
+                receipt.items.set(i as u32, assigned);
+            }
+        }
+
+        receipt.finalized = true;
+        let before = receipt.applied.clone();
+        let remainder_start =
+            Self::apply_receipt(&env, group_id, &mut receipt, &group.remainder_policy);
+
+        // Members claim items themselves, but unclaimed ones assigned here
+        // are charged without their say. Past the approval threshold the
+        // payer has to keep them or wait for members to claim them.
+        let mut assigned: i128 = 0;
+        for (member, share) in receipt.applied.iter() {
+            let previous = before
+                .iter()
+                .find(|(p, _)| *p == member)
+                .map(|(_, s)| s)
+                .unwrap_or(0);
+            if member != payer && share > previous {
+                assigned += share - previous;
+            }
+        }
+        if group.approval_threshold > 0 && assigned > group.approval_threshold {
+            return Err(Error::UnclaimedOverThreshold);
+        }
+
+        let full = Receipt {
+            items: receipt.items.clone(),
+            tax: receipt.tax,
+            tip: receipt.tip,
+        };
+        let split = Split::Itemized(full);
+        let mut amount = receipt.tax + receipt.tip;
+        for item in receipt.items.iter() {
+            amount += item.amount;
+        }
+        let expense_id = Self::store_expense(
+            &env,
+            group_id,
+            Expense {
+                id: 0,
+                kind: EntryKind::Expense,
+                payer: payer.clone(),
+                payers: Vec::from_array(&env, [(payer.clone(), amount)]),
+                amount,
+                asset: None,
+                conversion: Conversion::None,
+                participants: Self::split_participants(&env, &split),
+                split,
+                shares: receipt.applied.clone(),
+                remainder_policy: group.remainder_policy.clone(),
+                remainder_start,
+                from_pot: false,
+                details: Self::default_details(&env),
+                voided: false,
+                revisions: Vec::new(&env),
+                timestamp: env.ledger().timestamp(),
+            },
+        );
+        Self::save_receipt(&env, group_id, receipt);
+
+        Self::record_expense_activity(
+            &env,
+            group_id,
+            ActivityType::ReceiptFinalized,
+            payer.clone(),
+            amount,
+            None,
+            expense_id,
+        );
+
+        env.events().publish(
+            (symbol!("receipt"), symbol!("finalized")),
+            (group_id, receipt_id, payer, amount),
+        );
+
+        Ok(())
+    }
+
+    pub fn get_receipts(env: Env, group_id: u32) -> Vec<ClaimableReceipt> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Receipts(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn get_expenses(env: Env, group_id: u32) -> Vec<Expense> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Expenses(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn get_expense(env: Env, group_id: u32, expense_id: u32) -> Result<Expense, Error> {
+        Self::get_expenses(env, group_id)
+            .get(expense_id.wrapping_sub(1))
+            .ok_or(Error::ExpenseNotFound)
+    }
+
+    pub fn delete_group(env: Env, actor: Address, group_id: u32) -> Result<(), Error> {
+        actor.require_auth();
+
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if actor != group.creator {
+            return Err(Error::NotAuthorized);
+        }
+
+        Self::refund_pot(&env, group_id, &group);
+
+        for member in group.members.iter() {
+            let mut member_groups: Vec<u32> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::MemberGroups(member.clone()))
+                .unwrap_or(Vec::new(&env));
+
+            let mut i = 0;
+            let mut found = false;
+            while i < member_groups.len() {
+                if member_groups.get(i).unwrap() == group_id {
+                    member_groups.remove(i);
+                    found = true;
+                    break;
+                }
+                i += 1;
+            }
+
+            if found {
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::MemberGroups(member.clone()), &member_groups);
+            }
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Balance(group_id, member.clone()));
+            for asset in group.assets.iter() {
+                env.storage().persistent().remove(&DataKey::AssetBalance(
+                    group_id,
+                    member.clone(),
+                    asset,
+                ));
+            }
+            env.storage()
+                .persistent()
+                .remove(&DataKey::PotContribution(group_id, member.clone()));
+            env.storage()
+                .persistent()
+                .remove(&DataKey::PotSpent(group_id, member));
+        }
+
+        env.storage().persistent().remove(&DataKey::Group(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Expenses(group_id));
+        env.storage().persistent().remove(&DataKey::Pot(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PendingPayments(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PaymentCounter(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Receipts(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::RemainderCursor(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Recurring(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PendingExpenses(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PendingExpenseCounter(group_id));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Disputes(group_id));
+
+        env.events()
+            .publish((symbol!("group"), symbol!("deleted")), group_id);
+
+        Ok(())
+    }
+
+    pub fn get_activities(env: Env, group_id: u32) -> Vec<Activity> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Activities(group_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    fn balance_key(group_id: u32, member: Address, asset: &Option<Address>) -> DataKey {
+        match asset {
+            Some(asset) => DataKey::AssetBalance(group_id, member, asset.clone()),
+            None => DataKey::Balance(group_id, member),
+        }
+    }
+
+    fn has_open_balances(env: &Env, group_id: u32, group: &Group) -> bool {
+        for member in group.members.iter() {
+            let balance: i128 = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Balance(group_id, member.clone()))
+                .unwrap_or(0);
+            if balance != 0 {
+                return true;
+            }
+            for asset in group.assets.iter() {
+                let asset_balance: i128 = env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::AssetBalance(group_id, member.clone(), asset))
+                    .unwrap_or(0);
+                if asset_balance != 0 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // Pays out whatever is left in the pot in proportion to each member's
+    // unspent contribution; rounding dust goes to the first members listed.
+    fn refund_pot(env: &Env, group_id: u32, group: &Group) {
+        let pot: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Pot(group_id))
+            .unwrap_or(0);
+        let settlement_token = match group.settlement_token.clone() {
+            Some(settlement_token) if pot > 0 => settlement_token,
+            _ => return,
+        };
+
+        let summary = Self::get_pot(env.clone(), group_id).unwrap();
+        let mut total_remaining: i128 = 0;
+        for member in summary.members.iter() {
+            if member.remaining > 0 {
+                total_remaining += member.remaining;
+            }
+        }
+        if total_remaining <= 0 {
+            return;
+        }
+
+        let mut refunds: Vec<(Address, i128)> = Vec::new(env);
+        let mut paid: i128 = 0;
+        for member in summary.members.iter() {
+            if member.remaining > 0 {
+                let refund = pot * member.remaining / total_remaining;
+                paid += refund;
+                refunds.push_back((member.address, refund));
+            }
+        }
+        let mut dust = pot - paid;
+        let token_client = token::Client::new(env, &settlement_token);
+        for (member, refund) in refunds.iter() {
+            let mut refund = refund;
+            if dust > 0 {
+                refund += 1;
+                dust -= 1;
+            }
+            if refund > 0 {
+                token_client.transfer(&env.current_contract_address(), &member, &refund);
+                env.events().publish(
+                    (symbol!("pot"), symbol!("refund")),
+                    (group_id, member, refund),
+                );
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_expense(
+        env: &Env,
+        payer: Address,
+        group_id: u32,
+        asset: Option<Address>,
+        payers: Vec<(Address, i128)>,
+        split: Split,
+        details: ExpenseDetails,
+        from_pot: bool,
+    ) -> Result<(), Error> {
+        // The submitter signs for the record and every payer signs for the
+        // amount they are credited with.
+        payer.require_auth();
+        let mut signers = Vec::from_array(env, [payer.clone()]);
+        for (p, _) in payers.iter() {
+            if !signers.contains(&p) {
+                p.require_auth();
+                signers.push_back(p);
+            }
+        }
+
+        Self::post_expense(
+            env, payer, group_id, asset, payers, split, details, from_pot,
+        )
+    }
+
+    // Records an expense whose signatures have already been checked.
+    #[allow(clippy::too_many_arguments)]
+    fn post_expense(
+        env: &Env,
+        payer: Address,
+        group_id: u32,
+        asset: Option<Address>,
+        payers: Vec<(Address, i128)>,
+        split: Split,
+        details: ExpenseDetails,
+        from_pot: bool,
+    ) -> Result<(), Error> {
+        Self::validate_details(env, &details)?;
+        let (group, mut expense) = Self::build_expense(
+            env,
+            group_id,
+            EntryKind::Expense,
+            payer.clone(),
+            asset,
+            payers,
+            split,
+        )?;
+
+        if !group.members.contains(&payer) {
+            return Err(Error::NotAMember);
+        }
+
+        if from_pot {
+            if group.settlement_token.is_none() {
+                return Err(Error::SettlementTokenNotSet);
+            }
+            let pot: i128 = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Pot(group_id))
+                .unwrap_or(0);
+            if expense.amount > pot {
+                return Err(Error::InsufficientPot);
+            }
+            expense.from_pot = true;
+        }
+        expense.details = details;
+
+        let approvers = Self::required_approvers(env, &expense);
+        if Self::needs_approval(&group, &expense, &approvers) {
+            Self::hold_expense(env, group_id, &group, expense, approvers, None, None);
+            return Ok(());
+        }
+
+        if expense.from_pot {
+            Self::pay_from_pot(env, group_id, &group, &expense)?;
+        } else {
+            Self::apply_expense(env, group_id, &expense, 1);
+        }
+        Self::commit_expense(env, group_id, expense);
+
+        Ok(())
+    }
+
+    // Reimburses the payers of a pot expense from the kitty. Each participant's
+    // share has to fit within what they have put in and not yet spent.
+    fn pay_from_pot(
+        env: &Env,
+        group_id: u32,
+        group: &Group,
+        expense: &Expense,
+    ) -> Result<(), Error> {
+        let settlement_token = group
+            .settlement_token
+            .clone()
+            .ok_or(Error::SettlementTokenNotSet)?;
+        let pot: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Pot(group_id))
+            .unwrap_or(0);
+        if expense.amount > pot {
+            return Err(Error::InsufficientPot);
+        }
+
+        for (participant, share) in expense.shares.iter() {
+            let contributed: i128 = env
+                .storage()
+                .persistent()
+                .get(&DataKey::PotContribution(group_id, participant.clone()))
+                .unwrap_or(0);
+            let key = DataKey::PotSpent(group_id, participant);
+            let spent: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            if share > contributed - spent {
+                return Err(Error::InsufficientPot);
+            }
+            // A pot expense is consumed out of each participant's share of
+            // the kitty and leaves the member balances untouched.
+            env.storage().persistent().set(&key, &(spent + share));
+        }
+
+        // The payers fronted the money, so the kitty reimburses them.
+        let token_client = token::Client::new(env, &settlement_token);
+        for (p, paid) in expense.payers.iter() {
+            token_client.transfer(&env.current_contract_address(), &p, &paid);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::Pot(group_id), &(pot - expense.amount));
+
+        Ok(())
+    }
+
+    fn commit_expense(env: &Env, group_id: u32, expense: Expense) {
+        let payer = expense.payer.clone();
+        let amount = expense.amount;
+        let asset = expense.asset.clone();
+        let payers = expense.payers.clone();
+        let details = expense.details.clone();
+        let (kind, topic) = match expense.kind {
+            EntryKind::Expense => (ActivityType::Expense, symbol!("expense")),
+            EntryKind::Income => (ActivityType::Income, symbol!("income")),
+        };
+        let expense_id = Self::store_expense(env, group_id, expense);
+
+        for (p, paid) in payers.iter() {
+            Self::record_expense_activity(
+                env,
+                group_id,
+                kind.clone(),
+                p,
+                paid,
+                asset.clone(),
+                expense_id,
+            );
+        }
+
+        env.events().publish(
+            (topic, symbol!("added")),
+            (group_id, expense_id, payer, amount, asset, payers, details),
+        );
+    }
+
+    // Participants who are charged for an expense they did not help pay.
+    fn required_approvers(env: &Env, expense: &Expense) -> Vec<Address> {
+        let mut approvers = Vec::new(env);
+        if expense.kind == EntryKind::Income {
+            return approvers;
+        }
+        for (participant, share) in expense.shares.iter() {
+            if share > 0 && !expense.payers.iter().any(|(p, _)| p == participant) {
+                approvers.push_back(participant);
+            }
+        }
+        approvers
+    }
+
+    // Large expenses wait for the members they charge before they reach the
+    // balances; the threshold is in the expense's ledger. The kitty pays out
+    // real tokens, so pot expenses always wait.
+    fn needs_approval(group: &Group, expense: &Expense, approvers: &Vec<Address>) -> bool {
+        if approvers.is_empty() {
+            return false;
+        }
+        expense.from_pot
+            || (group.approval_threshold > 0 && expense.amount > group.approval_threshold)
+    }
+
+    fn hold_expense(
+        env: &Env,
+        group_id: u32,
+        group: &Group,
+        expense: Expense,
+        approvers: Vec<Address>,
+        replaces: Option<u32>,
+        editor: Option<Address>,
+    ) {
+        // Pot payouts move tokens, so they wait for everyone they charge.
+        let required = if expense.from_pot
+            || group.approval_quorum == 0
+            || group.approval_quorum > approvers.len()
+        {
+            approvers.len()
+        } else {
+            group.approval_quorum
+        };
+        let expires_at = if group.approval_window == 0 {
+            0
+        } else {
+            env.ledger().timestamp() + group.approval_window
+        };
+
+        let id: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::PendingExpenseCounter(group_id))
+            .unwrap_or(0)
+            + 1;
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingExpenseCounter(group_id), &id);
+
+        let payer = expense.payer.clone();
+        let amount = expense.amount;
+        let mut pending = Self::get_pending_expenses(env.clone(), group_id);
+        pending.push_back(PendingExpense {
+            id,
+            expense,
+            approvers,
+            approvals: Vec::new(env),
+            required,
+            expires_at,
+            replaces,
+            editor,
+        });
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingExpenses(group_id), &pending);
+
+        env.events().publish(
+            (symbol!("expense"), symbol!("pending")),
+            (group_id, id, payer, amount, required, expires_at, replaces),
+        );
+    }
+
+    fn load_pending_expense(
+        env: &Env,
+        member: &Address,
+        group_id: u32,
+        pending_id: u32,
+    ) -> Result<(Vec<PendingExpense>, u32, PendingExpense), Error> {
+        if !env.storage().persistent().has(&DataKey::Group(group_id)) {
+            return Err(Error::GroupNotFound);
+        }
+
+        let pending = Self::get_pending_expenses(env.clone(), group_id);
+        let index = pending
+            .iter()
+            .position(|p| p.id == pending_id)
+            .ok_or(Error::PendingExpenseNotFound)? as u32;
+        let entry = pending.get(index).unwrap();
+
+        if !entry.approvers.contains(member) {
+            return Err(Error::NotAuthorized);
+        }
+
+        if entry.expires_at != 0 && env.ledger().timestamp() > entry.expires_at {
+            return Err(Error::PendingExpired);
+        }
+
+        Ok((pending, index, entry))
+    }
+
+    // Validates an expense and works out its shares without touching any
+    // balances. New foreign assets are registered on the group.
+    fn build_expense(
+        env: &Env,
+        group_id: u32,
+        kind: EntryKind,
+        payer: Address,
+        asset: Option<Address>,
+        payers: Vec<(Address, i128)>,
+        split: Split,
+    ) -> Result<(Group, Expense), Error> {
+        if payers.is_empty() {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut amount: i128 = 0;
+        for (_, paid) in payers.iter() {
+            if paid <= 0 {
+                return Err(Error::InvalidAmount);
+            }
+            amount += paid;
+        }
+
+        let mut group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        for (p, _) in payers.iter() {
+            if !group.members.contains(&p) {
+                return Err(Error::NotAMember);
+            }
+        }
+
+        let participants = Self::split_participants(env, &split);
+        for p in participants.iter() {
+            if !group.members.contains(&p) {
+                return Err(Error::NotAMember);
+            }
+        }
+
+        if participants.is_empty() {
+            return Err(Error::InvalidAmount);
+        }
+
+        Self::validate_split(&split, amount)?;
+
+        // Expenses in the settlement token belong on the main ledger. Other
+        // assets are converted into it when the group has a price oracle,
+        // and otherwise get a ledger of their own, registered on first use.
+        let mut asset = asset.filter(|a| group.settlement_token.as_ref() != Some(a));
+        let mut payers = payers;
+        let mut conversion = Conversion::None;
+        if let (Some(foreign), Some(_)) = (asset.clone(), group.price_oracle.clone()) {
+            let rate = Self::convert(env, &group, foreign, amount)?;
+            amount = rate.0;
+            // The payers' credit is rounded by the group's policy as well; an
+            // absorbing first payer passes the extra credit on.
+            let (first, _) = payers.get(0).unwrap();
+            let (mut credits, leftover) = Self::allocate(env, amount, &payers);
+            Self::hand_out_leftover(
+                env,
+                group_id,
+                &group.remainder_policy,
+                &first,
+                false,
+                &mut credits,
+                leftover,
+            );
+            payers = credits;
+            conversion = Conversion::Oracle(rate.1);
+            asset = None;
+        }
+        if let Some(asset) = asset.clone() {
+            if !group.assets.contains(&asset) {
+                group.assets.push_back(asset);
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::Group(group_id), &group);
+            }
+        }
+
+        let (mut shares, leftover) = Self::compute_shares(env, amount, &split);
+        let (absorber, _) = payers.get(0).unwrap();
+        let remainder_start = Self::hand_out_leftover(
+            env,
+            group_id,
+            &group.remainder_policy,
+            &absorber,
+            kind == EntryKind::Expense,
+            &mut shares,
+            leftover,
+        );
+
+        let expense = Expense {
+            id: 0,
+            kind,
+            payer,
+            payers,
+            amount,
+            asset,
+            conversion,
+            participants,
+            split,
+            shares,
+            remainder_policy: group.remainder_policy.clone(),
+            remainder_start,
+            from_pot: false,
+            details: Self::default_details(env),
+            voided: false,
+            revisions: Vec::new(env),
+            timestamp: env.ledger().timestamp(),
+        };
+        Ok((group, expense))
+    }
+
+    fn discard_expense(
+        env: &Env,
+        group_id: u32,
+        mut expenses: Vec<Expense>,
+        mut expense: Expense,
+        actor: Address,
+    ) {
+        Self::apply_expense(env, group_id, &expense, -1);
+        expense.voided = true;
+        let expense_id = expense.id;
+        let amount = expense.amount;
+        let asset = expense.asset.clone();
+        expenses.set(expense_id - 1, expense);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Expenses(group_id), &expenses);
+
+        Self::record_expense_activity(
+            env,
+            group_id,
+            ActivityType::ExpenseVoided,
+            actor.clone(),
+            amount,
+            asset,
+            expense_id,
+        );
+
+        env.events().publish(
+            (symbol!("expense"), symbol!("voided")),
+            (group_id, expense_id, actor, amount),
+        );
+    }
+
+    // Marks the open dispute on an expense, if there is one, as settled by
+    // the payer or creator changing the expense.
+    fn close_dispute(
+        env: &Env,
+        group_id: u32,
+        expense_id: u32,
+        actor: Address,
+        status: DisputeStatus,
+    ) {
+        let mut disputes = Self::get_disputes(env.clone(), group_id);
+        let Some(index) = disputes
+            .iter()
+            .position(|d| d.expense_id == expense_id && d.status == DisputeStatus::Open)
+        else {
+            return;
+        };
+
+        let mut dispute = disputes.get(index as u32).unwrap();
+        dispute.status = status.clone();
+        disputes.set(index as u32, dispute);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Disputes(group_id), &disputes);
+
+        let expense = Self::get_expense(env.clone(), group_id, expense_id).unwrap();
+        Self::record_expense_activity(
+            env,
+            group_id,
+            ActivityType::DisputeResolved,
+            actor.clone(),
+            expense.amount,
+            expense.asset,
+            expense_id,
+        );
+
+        env.events().publish(
+            (symbol!("dispute"), symbol!("resolved")),
+            (group_id, expense_id, actor, status),
+        );
+    }
+
+    // Settles a dispute once a majority of the expense's participants agree.
+    // Upholding it voids the expense.
+    fn dispute_electorate(expense: &Expense) -> Vec<Address> {
+        let mut voters = expense.participants.clone();
+        for (p, _) in expense.payers.iter() {
+            if !voters.contains(&p) {
+                voters.push_back(p);
+            }
+        }
+        voters
+    }
+
+    fn tally_dispute(env: &Env, group_id: u32, index: u32, actor: Address) {
+        let mut disputes = Self::get_disputes(env.clone(), group_id);
+        let mut dispute = disputes.get(index).unwrap();
+        let expenses = Self::get_expenses(env.clone(), group_id);
+        let expense = expenses.get(dispute.expense_id - 1).unwrap();
+
+        // The payers get a say too, and no single vote decides a dispute.
+        // When everyone has voted without a majority to uphold, as with a
+        // split two-person vote, the expense stands.
+        let electorate = Self::dispute_electorate(&expense).len();
+        let majority = (electorate / 2 + 1).max(2);
+        let votes = dispute.upheld_by.len() + dispute.dismissed_by.len();
+        let kind = if dispute.upheld_by.len() >= majority {
+            dispute.status = DisputeStatus::Upheld;
+            ActivityType::DisputeUpheld
+        } else if dispute.dismissed_by.len() >= majority || votes >= electorate {
+            dispute.status = DisputeStatus::Dismissed;
+            ActivityType::DisputeDismissed
+        } else {
+            return;
+        };
+
+        let expense_id = dispute.expense_id;
+        let status = dispute.status.clone();
+        disputes.set(index, dispute);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Disputes(group_id), &disputes);
+
+        Self::record_expense_activity(
+            env,
+            group_id,
+            kind,
+            actor.clone(),
+            expense.amount,
+            expense.asset.clone(),
+            expense_id,
+        );
+
+        env.events().publish(
+            (symbol!("dispute"), symbol!("resolved")),
+            (group_id, expense_id, actor.clone(), status.clone()),
+        );
+
+        if status == DisputeStatus::Upheld {
+            Self::discard_expense(env, group_id, expenses, expense, actor);
+        }
+    }
+
+    // What an expense did to one member's balance: paid minus consumed.
+    fn expense_delta(expense: &Expense, member: &Address) -> i128 {
+        let mut delta: i128 = 0;
+        for (p, paid) in expense.payers.iter() {
+            if p == *member {
+                delta += paid;
+            }
+        }
+        for (participant, share) in expense.shares.iter() {
+            if participant == *member {
+                delta -= share;
+            }
+        }
+        match expense.kind {
+            EntryKind::Expense => delta,
+            EntryKind::Income => -delta,
+        }
+    }
+
+    fn add_category_member(
+        members: &mut Vec<CategoryMember>,
+        address: Address,
+        paid: i128,
+        consumed: i128,
+    ) {
+        match members.iter().position(|m| m.address == address) {
+            Some(i) => {
+                let mut member = members.get(i as u32).unwrap();
+                member.paid += paid;
+                member.consumed += consumed;
+                members.set(i as u32, member);
+            }
+            None => members.push_back(CategoryMember {
+                address,
+                paid,
+                consumed,
+            }),
+        }
+    }
+
+    fn default_details(env: &Env) -> ExpenseDetails {
+        ExpenseDetails {
+            title: String::from_str(env, ""),
+            note: None,
+            category: String::from_str(env, ""),
+            occurred_at: env.ledger().timestamp(),
+            receipt_hash: None,
+            receipt_uri: None,
+        }
+    }
+
+    fn validate_details(env: &Env, details: &ExpenseDetails) -> Result<(), Error> {
+        if details.title.len() > MAX_TITLE_LEN {
+            return Err(Error::TitleTooLong);
+        }
+        if let Some(note) = &details.note {
+            if note.len() > MAX_NOTE_LEN {
+                return Err(Error::NoteTooLong);
+            }
+        }
+        if details.category.len() > MAX_CATEGORY_LEN {
+            return Err(Error::CategoryTooLong);
+        }
+        if details.occurred_at > env.ledger().timestamp() {
+            return Err(Error::InvalidDate);
+        }
+        if let Some(uri) = &details.receipt_uri {
+            if uri.len() > MAX_URI_LEN {
+                return Err(Error::UriTooLong);
+            }
+        }
+        Ok(())
+    }
+
+    // Credits the payers and debits the participants of an expense, or takes
+    // those entries back again when `sign` is -1.
+    fn apply_expense(env: &Env, group_id: u32, expense: &Expense, sign: i128) {
+        let sign = match expense.kind {
+            EntryKind::Expense => sign,
+            EntryKind::Income => -sign,
+        };
+        for (p, paid) in expense.payers.iter() {
+            let key = Self::balance_key(group_id, p, &expense.asset);
+            let current_balance: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            env.storage()
+                .persistent()
+                .set(&key, &(current_balance + sign * paid));
+        }
+
+        for (participant, share) in expense.shares.iter() {
+            let key = Self::balance_key(group_id, participant, &expense.asset);
+            let current_balance: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            env.storage()
+                .persistent()
+                .set(&key, &(current_balance - sign * share));
+        }
+    }
+
+    fn load_editable_expense(
+        env: &Env,
+        actor: &Address,
+        group_id: u32,
+        expense_id: u32,
+    ) -> Result<(Vec<Expense>, Expense), Error> {
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        let expenses = Self::get_expenses(env.clone(), group_id);
+        let expense = expenses
+            .get(expense_id.wrapping_sub(1))
+            .ok_or(Error::ExpenseNotFound)?;
+
+        // Whoever submitted or paid for the expense may change it, and so
+        // may the group creator.
+        if *actor != group.creator
+            && *actor != expense.payer
+            && !expense.payers.iter().any(|(p, _)| p == *actor)
+        {
+            return Err(Error::NotAuthorized);
+        }
+
+        if expense.voided {
+            return Err(Error::ExpenseVoided);
+        }
+
+        // The kitty has already paid the payers back for pot expenses.
+        if expense.from_pot {
+            return Err(Error::ExpenseNotEditable);
+        }
+
+        Ok((expenses, expense))
+    }
+
+    fn load_receipt_item(
+        env: &Env,
+        member: &Address,
+        group_id: u32,
+        receipt_id: u32,
+        item_index: u32,
+    ) -> Result<(ClaimableReceipt, LineItem), Error> {
+        let group: Group = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Group(group_id))
+            .ok_or(Error::GroupNotFound)?;
+
+        if !group.members.contains(member) {
+            return Err(Error::NotAMember);
+        }
+
+        let receipt = Self::get_receipts(env.clone(), group_id)
+            .get(receipt_id.wrapping_sub(1))
+            .ok_or(Error::ReceiptNotFound)?;
+
+        if receipt.finalized {
+            return Err(Error::ReceiptFinalized);
+        }
+
+        let item = receipt
+            .items
+            .get(item_index)
+            .ok_or(Error::ReceiptNotFound)?;
+
+        Ok((receipt, item))
+    }
+
+    fn save_receipt(env: &Env, group_id: u32, receipt: ClaimableReceipt) {
+        let mut receipts = Self::get_receipts(env.clone(), group_id);
+        receipts.set(receipt.id - 1, receipt);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Receipts(group_id), &receipts);
+    }
+
+    // Charges claimants for the items claimed so far, plus the matching slice
+    // of tax and tip, and credits the payer. Only the difference from what
+    // was already applied touches the balances. While items are still being
+    // claimed the rounding goes to the front of the list; the group's
+    // remainder policy settles it once the receipt is finalized.
+    fn apply_receipt(
+        env: &Env,
+        group_id: u32,
+        receipt: &mut ClaimableReceipt,
+        policy: &RemainderPolicy,
+    ) -> u32 {
+        let mut claimed = Receipt {
+            items: Vec::new(env),
+            tax: 0,
+            tip: 0,
+        };
+        let mut items_total: i128 = 0;
+        let mut claimed_total: i128 = 0;
+        for item in receipt.items.iter() {
+            items_total += item.amount;
+            if !item.participants.is_empty() {
+                claimed_total += item.amount;
+                claimed.items.push_back(item);
+            }
+        }
+        claimed.tax = (receipt.tax + receipt.tip) * claimed_total / items_total;
+
+        let mut remainder_start: u32 = 0;
+        let target = if claimed.items.is_empty() {
+            Vec::new(env)
+        } else {
+            let (mut shares, leftover) = Self::receipt_shares(env, &claimed);
+            remainder_start = Self::hand_out_leftover(
+                env,
+                group_id,
+                policy,
+                &receipt.payer,
+                true,
+                &mut shares,
+                leftover,
+            );
+            shares
+        };
+
+        let mut delta_total: i128 = 0;
+        for (participant, share) in target.iter() {
+            let previous = receipt
+                .applied
+                .iter()
+                .find(|(p, _)| *p == participant)
+                .map(|(_, s)| s)
+                .unwrap_or(0);
+            Self::adjust_balance(env, group_id, &participant, previous - share);
+            delta_total += share - previous;
+        }
+        for (participant, previous) in receipt.applied.iter() {
+            if !target.iter().any(|(p, _)| p == participant) {
+                Self::adjust_balance(env, group_id, &participant, previous);
+                delta_total -= previous;
+            }
+        }
+        Self::adjust_balance(env, group_id, &receipt.payer, delta_total);
+
+        receipt.applied = target;
+        remainder_start
+    }
+
+    fn applied_share(receipt: &ClaimableReceipt, member: &Address) -> i128 {
+        receipt
+            .applied
+            .iter()
+            .find(|(p, _)| p == member)
+            .map(|(_, share)| share)
+            .unwrap_or(0)
+    }
+
+    fn adjust_balance(env: &Env, group_id: u32, member: &Address, delta: i128) {
+        if delta == 0 {
+            return;
+        }
+        let key = DataKey::Balance(group_id, member.clone());
+        let balance: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(balance + delta));
+    }
+
+    fn store_expense(env: &Env, group_id: u32, mut expense: Expense) -> u32 {
+        let mut expenses: Vec<Expense> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Expenses(group_id))
+            .unwrap_or(Vec::new(env));
+        // Expenses are never removed, so the position doubles as a stable id.
+        expense.id = expenses.len() + 1;
+        expenses.push_back(expense);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Expenses(group_id), &expenses);
+        expenses.len()
+    }
+
+    fn split_participants(env: &Env, split: &Split) -> Vec<Address> {
+        let mut participants = Vec::new(env);
+        match split {
+            Split::Equal(addresses) => participants = addresses.clone(),
+            Split::Weighted(weights) | Split::Percent(weights) => {
+                for (participant, _) in weights.iter() {
+                    participants.push_back(participant);
+                }
+            }
+            Split::Exact(shares) => {
+                for (participant, _) in shares.iter() {
+                    participants.push_back(participant);
+                }
+            }
+            Split::Itemized(receipt) => {
+                for item in receipt.items.iter() {
+                    for participant in item.participants.iter() {
+                        if !participants.contains(&participant) {
+                            participants.push_back(participant);
+                        }
+                    }
+                }
+            }
+        }
+        participants
+    }
+
+    fn validate_split(split: &Split, amount: i128) -> Result<(), Error> {
+        match split {
+            Split::Equal(_) => {}
+            Split::Weighted(weights) => {
+                for (_, weight) in weights.iter() {
+                    if weight == 0 {
+                        return Err(Error::InvalidWeight);
+                    }
+                }
+            }
+            Split::Exact(shares) => {
+                let mut total: i128 = 0;
+                for (_, share) in shares.iter() {
+                    if share <= 0 {
+                        return Err(Error::InvalidShare);
+                    }
+                    total += share;
+                }
+                if total != amount {
+                    return Err(Error::SharesMismatch);
+                }
+            }
+            Split::Percent(basis_points) => {
+                let mut total: u32 = 0;
+                for (_, bps) in basis_points.iter() {
+                    if bps == 0 {
+                        return Err(Error::InvalidPercentages);
+                    }
+                    total = total.saturating_add(bps);
+                }
+                if total != 10_000 {
+                    return Err(Error::InvalidPercentages);
+                }
+            }
+            Split::Itemized(receipt) => {
+                if receipt.items.is_empty() || receipt.tax < 0 || receipt.tip < 0 {
+                    return Err(Error::InvalidAmount);
+                }
+                let mut total = receipt.tax + receipt.tip;
+                for item in receipt.items.iter() {
+                    if item.amount <= 0 || item.participants.is_empty() {
+                        return Err(Error::InvalidAmount);
+                    }
+                    total += item.amount;
+                }
+                if total != amount {
+                    return Err(Error::SharesMismatch);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Exact shares are used as weights too: they come back unchanged unless
+    // an oracle conversion has rescaled the total. The rounded-down shares are
+    // returned with the leftover units for the group's remainder policy.
+    fn compute_shares(env: &Env, amount: i128, split: &Split) -> (Vec<(Address, i128)>, i128) {
+        let mut weights: Vec<(Address, i128)> = Vec::new(env);
+        match split {
+            Split::Equal(participants) => {
+                for participant in participants.iter() {
+                    weights.push_back((participant, 1));
+                }
+            }
+            Split::Weighted(entries) | Split::Percent(entries) => {
+                for (participant, weight) in entries.iter() {
+                    weights.push_back((participant, weight as i128));
+                }
+            }
+            Split::Exact(shares) => weights = shares.clone(),
+            Split::Itemized(receipt) => return Self::receipt_shares(env, receipt),
+        }
+
+        Self::allocate(env, amount, &weights)
+    }
+
+    // Rounds every item and the tax and tip down, returning the shares along
+    // with all the leftover units for the remainder policy to hand out.
+    fn receipt_shares(env: &Env, receipt: &Receipt) -> (Vec<(Address, i128)>, i128) {
+        let mut subtotals: Vec<(Address, i128)> = Vec::new(env);
+        let mut leftover: i128 = 0;
+        for item in receipt.items.iter() {
+            let mut item_weights: Vec<(Address, i128)> = Vec::new(env);
+            for participant in item.participants.iter() {
+                item_weights.push_back((participant, 1));
+            }
+            let (item_shares, item_leftover) = Self::allocate(env, item.amount, &item_weights);
+            leftover += item_leftover;
+            for (participant, share) in item_shares.iter() {
+                match subtotals.iter().position(|(p, _)| p == participant) {
+                    Some(i) => {
+                        let (_, subtotal) = subtotals.get(i as u32).unwrap();
+                        subtotals.set(i as u32, (participant, subtotal + share));
+                    }
+                    None => subtotals.push_back((participant, share)),
+                }
+            }
+        }
+
+        let (extras, extra_leftover) = Self::allocate(env, receipt.tax + receipt.tip, &subtotals);
+        let mut shares: Vec<(Address, i128)> = Vec::new(env);
+        for (i, (participant, subtotal)) in subtotals.iter().enumerate() {
+            let (_, extra) = extras.get(i as u32).unwrap();
+            shares.push_back((participant, subtotal + extra));
+        }
+        (shares, leftover + extra_leftover)
+    }
+
+    fn allocate(
+        env: &Env,
+        amount: i128,
+        weights: &Vec<(Address, i128)>,
+    ) -> (Vec<(Address, i128)>, i128) {
+        let mut total_weight: i128 = 0;
+        for (_, weight) in weights.iter() {
+            total_weight += weight;
+        }
+
+        let mut shares: Vec<(Address, i128)> = Vec::new(env);
+        let mut allocated: i128 = 0;
+        for (participant, weight) in weights.iter() {
+            let share = amount * weight / total_weight;
+            allocated += share;
+            shares.push_back((participant, share));
+        }
+
+        (shares, amount - allocated)
+    }
+
+    // Hands out the units left over after rounding shares down, following
+    // the group's policy, and returns the index it started from. The absorber
+    // is the first payer: with PayerAbsorbs they take the units themselves
+    // when `takes` is set, and otherwise pass them on to everyone else.
+    fn hand_out_leftover(
+        env: &Env,
+        group_id: u32,
+        policy: &RemainderPolicy,
+        absorber: &Address,
+        takes: bool,
+        shares: &mut Vec<(Address, i128)>,
+        leftover: i128,
+    ) -> u32 {
+        match policy {
+            RemainderPolicy::FirstListed => {
+                Self::hand_out(shares, leftover, 0);
+                0
+            }
+            RemainderPolicy::PayerAbsorbs if takes => {
+                match shares.iter().position(|(p, _)| p == *absorber) {
+                    Some(i) => {
+                        let (_, share) = shares.get(i as u32).unwrap();
+                        shares.set(i as u32, (absorber.clone(), share + leftover));
+                    }
+                    None if leftover > 0 => shares.push_back((absorber.clone(), leftover)),
+                    None => {}
+                }
+                0
+            }
+            RemainderPolicy::PayerAbsorbs => {
+                match shares.iter().position(|(p, _)| p == *absorber) {
+                    Some(i) if shares.len() > 1 => {
+                        let own = shares.get(i as u32).unwrap();
+                        shares.remove(i as u32);
+                        Self::hand_out(shares, leftover, 0);
+                        shares.insert(i as u32, own);
+                    }
+                    _ => Self::hand_out(shares, leftover, 0),
+                }
+                0
+            }
+            RemainderPolicy::Rotate => {
+                let cursor: u32 = env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::RemainderCursor(group_id))
+                    .unwrap_or(0);
+                let start = cursor % shares.len();
+                Self::hand_out(shares, leftover, start);
+                env.storage().persistent().set(
+                    &DataKey::RemainderCursor(group_id),
+                    &(start + leftover as u32),
+                );
+                start
+            }
+            RemainderPolicy::Random => {
+                let start = env.prng().gen_range::<u64>(0..shares.len() as u64) as u32;
+                Self::hand_out(shares, leftover, start);
+                start
+            }
+        }
+    }
 
-        let mut member_groups: Vec<u32> = env
-            .storage()
-            .persistent()
-            .get(&DataKey::MemberGroups(new_member.clone()))
-            .unwrap_or(Vec::new(&env));
-        if !member_groups.contains(&group_id) {
-            member_groups.push_back(group_id);
-            env.storage()
-                .persistent()
-                .set(&DataKey::MemberGroups(new_member.clone()), &member_groups);
+    // Gives one extra unit to each share in turn, starting at `start` and
+    // wrapping around the end of the list.
+    fn hand_out(shares: &mut Vec<(Address, i128)>, remainder: i128, start: u32) {
+        let mut i = start;
+        for _ in 0..remainder {
+            let (participant, share) = shares.get(i).unwrap();
+            shares.set(i, (participant, share + 1));
+            i = (i + 1) % shares.len();
         }
+    }
 
-        env.events().publish(
-            (symbol!("group"), symbol!("mem_add")),
-            (group_id, new_member.clone(), actor.clone()),
-        );
+    fn convert(
+        env: &Env,
+        group: &Group,
+        asset: Address,
+        amount: i128,
+    ) -> Result<(i128, OracleRate), Error> {
+        let base = group
+            .settlement_token
+            .clone()
+            .ok_or(Error::SettlementTokenNotSet)?;
+        let oracle = PriceOracleClient::new(env, &group.price_oracle.clone().unwrap());
+        let now = env.ledger().timestamp();
+
+        let quote = oracle
+            .lastprice(&OracleAsset::Stellar(asset.clone()))
+            .ok_or(Error::PriceUnavailable)?;
+        let base_quote = oracle
+            .lastprice(&OracleAsset::Stellar(base.clone()))
+            .ok_or(Error::PriceUnavailable)?;
+        if quote.price <= 0 || base_quote.price <= 0 {
+            return Err(Error::PriceUnavailable);
+        }
 
-        Self::record_activity(
-            &env,
-            group_id,
-            ActivityType::MemberAdded,
-            actor,
-            Some(new_member),
-            0,
-        );
+        let timestamp = quote.timestamp.min(base_quote.timestamp);
+        if now.saturating_sub(timestamp) > group.max_price_age {
+            return Err(Error::StalePrice);
+        }
 
-        Ok(())
+        // Both prices are quoted in the oracle's own unit, so the cross rate
+        // is price / base_price, adjusted for the two tokens' decimals.
+        let decimals = token::Client::new(env, &asset).decimals();
+        let base_decimals = token::Client::new(env, &base).decimals();
+        let converted = amount * quote.price * 10i128.pow(base_decimals)
+            / (base_quote.price * 10i128.pow(decimals));
+        if converted <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        Ok((
+            converted,
+            OracleRate {
+                asset,
+                amount,
+                price: quote.price,
+                base_price: base_quote.price,
+                timestamp,
+            },
+        ))
     }
 
-    pub fn settle_debt(
-        env: Env,
+    fn settle(
+        env: &Env,
         from: Address,
         group_id: u32,
         to: Address,
+        asset: Option<Address>,
         amount: i128,
     ) -> Result<(), Error> {
         from.require_auth();
 
+        let (group, asset) = Self::check_settlement(env, &from, group_id, &to, asset, amount)?;
+
+        // Without a token nothing moves on-chain, so the creditor has to
+        // agree that they were paid.
+        let token_id = Self::transfer_token(&group, &asset);
+        if token_id.is_none() {
+            to.require_auth();
+        }
+
+        Self::execute_settlement(
+            env,
+            group_id,
+            from.clone(),
+            to.clone(),
+            asset.clone(),
+            amount,
+            token_id,
+        );
+
+        env.events().publish(
+            (symbol!("debt"), symbol!("settled")),
+            (group_id, from, to, amount, asset),
+        );
+
+        Ok(())
+    }
+
+    fn check_settlement(
+        env: &Env,
+        from: &Address,
+        group_id: u32,
+        to: &Address,
+        asset: Option<Address>,
+        amount: i128,
+    ) -> Result<(Group, Option<Address>), Error> {
         if amount <= 0 {
             return Err(Error::InvalidAmount);
         }
@@ -379,14 +3796,15 @@ impl LumenSplit {
             .get(&DataKey::Group(group_id))
             .ok_or(Error::GroupNotFound)?;
 
-        if !group.members.contains(&from) || !group.members.contains(&to) {
+        if !group.members.contains(from) || !group.members.contains(to) {
             return Err(Error::NotAMember);
         }
 
+        let asset = asset.filter(|a| group.settlement_token.as_ref() != Some(a));
         let from_balance: i128 = env
             .storage()
             .persistent()
-            .get(&DataKey::Balance(group_id, from.clone()))
+            .get(&Self::balance_key(group_id, from.clone(), &asset))
             .unwrap_or(0);
 
         if from_balance >= 0 {
@@ -397,106 +3815,140 @@ impl LumenSplit {
             return Err(Error::InvalidAmount);
         }
 
+        // Paying someone who is not owed that much would push them into
+        // debt they never agreed to.
         let to_balance: i128 = env
             .storage()
             .persistent()
-            .get(&DataKey::Balance(group_id, to.clone()))
+            .get(&Self::balance_key(group_id, to.clone(), &asset))
             .unwrap_or(0);
 
-        env.storage().persistent().set(
-            &DataKey::Balance(group_id, from.clone()),
-            &(from_balance + amount),
-        );
-        env.storage().persistent().set(
-            &DataKey::Balance(group_id, to.clone()),
-            &(to_balance - amount),
-        );
-
-        env.events().publish(
-            (symbol!("debt"), symbol!("settled")),
-            (group_id, from.clone(), to.clone(), amount),
-        );
-
-        Self::record_activity(
-            &env,
-            group_id,
-            ActivityType::Settlement,
-            from,
-            Some(to),
-            amount,
-        );
+        if to_balance <= 0 || amount > to_balance {
+            return Err(Error::InvalidCreditor);
+        }
 
-        Ok(())
+        Ok((group, asset))
     }
 
-    pub fn get_balance(env: Env, group_id: u32, member: Address) -> i128 {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Balance(group_id, member))
-            .unwrap_or(0)
-    }
+    fn take_pending_payment(
+        env: &Env,
+        to: &Address,
+        group_id: u32,
+        payment_id: u32,
+    ) -> Result<PendingPayment, Error> {
+        let mut pending = Self::get_pending_payments(env.clone(), group_id);
+        let index = pending
+            .iter()
+            .position(|p| p.id == payment_id)
+            .ok_or(Error::PaymentNotFound)?;
+        let payment = pending.get(index as u32).unwrap();
+
+        if payment.to != *to {
+            return Err(Error::NotAuthorized);
+        }
 
-    pub fn get_group(env: Env, group_id: u32) -> Result<Group, Error> {
+        pending.remove(index as u32);
         env.storage()
             .persistent()
-            .get(&DataKey::Group(group_id))
-            .ok_or(Error::GroupNotFound)
-    }
+            .set(&DataKey::PendingPayments(group_id), &pending);
 
-    pub fn get_group_count(env: Env) -> u32 {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Counter)
-            .unwrap_or(0)
+        Ok(payment)
     }
 
-    pub fn get_group_with_balances(env: Env, group_id: u32) -> Result<GroupWithBalances, Error> {
-        let group: Group = env
+    // What `other` owes `member` in one group: the most either of them could
+    // settle with the other without crossing zero. Negative when `member`
+    // is the one who owes.
+    fn pair_position(env: &Env, group_id: u32, member: &Address, other: &Address) -> i128 {
+        let group: Option<Group> = env.storage().persistent().get(&DataKey::Group(group_id));
+        match group {
+            Some(group) if group.members.contains(other) && member != other => {}
+            _ => return 0,
+        }
+
+        let member_balance: i128 = env
             .storage()
             .persistent()
-            .get(&DataKey::Group(group_id))
-            .ok_or(Error::GroupNotFound)?;
-
-        let mut members = Vec::new(&env);
-        for member_addr in group.members.iter() {
-            let balance: i128 = env
-                .storage()
-                .persistent()
-                .get(&DataKey::Balance(group_id, member_addr.clone()))
-                .unwrap_or(0);
-
-            let name = Self::get_user_name(env.clone(), member_addr.clone());
+            .get(&DataKey::Balance(group_id, member.clone()))
+            .unwrap_or(0);
+        let other_balance: i128 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Balance(group_id, other.clone()))
+            .unwrap_or(0);
 
-            members.push_back(MemberInfo {
-                address: member_addr,
-                name,
-                balance,
-            });
+        if member_balance > 0 && other_balance < 0 {
+            member_balance.min(-other_balance)
+        } else if member_balance < 0 && other_balance > 0 {
+            -(other_balance.min(-member_balance))
+        } else {
+            0
         }
+    }
 
-        Ok(GroupWithBalances {
-            name: group.name,
-            members,
-            creator: group.creator,
-        })
+    fn transfer_token(group: &Group, asset: &Option<Address>) -> Option<Address> {
+        asset.clone().or(group.settlement_token.clone())
     }
 
-    pub fn get_settlements(env: Env, group_id: u32) -> Result<Vec<Settlement>, Error> {
-        let group: Group = env
-            .storage()
+    fn execute_settlement(
+        env: &Env,
+        group_id: u32,
+        from: Address,
+        to: Address,
+        asset: Option<Address>,
+        amount: i128,
+        token_id: Option<Address>,
+    ) {
+        let from_key = Self::balance_key(group_id, from.clone(), &asset);
+        let to_key = Self::balance_key(group_id, to.clone(), &asset);
+        let from_balance: i128 = env.storage().persistent().get(&from_key).unwrap_or(0);
+        let to_balance: i128 = env.storage().persistent().get(&to_key).unwrap_or(0);
+
+        env.storage()
             .persistent()
-            .get(&DataKey::Group(group_id))
-            .ok_or(Error::GroupNotFound)?;
+            .set(&from_key, &(from_balance + amount));
+        env.storage()
+            .persistent()
+            .set(&to_key, &(to_balance - amount));
+
+        // Move the funds in the same invocation so the ledger and the token
+        // balances either both change or neither does.
+        if let Some(token_id) = token_id {
+            token::Client::new(env, &token_id).transfer(&from, &to, &amount);
+        }
+
+        Self::record_activity(
+            env,
+            group_id,
+            ActivityType::Settlement,
+            from,
+            Some(to),
+            amount,
+            asset,
+        );
+    }
 
-        let mut debtors: Vec<(Address, i128)> = Vec::new(&env);
-        let mut creditors: Vec<(Address, i128)> = Vec::new(&env);
+    fn plan_settlements(
+        env: &Env,
+        group_id: u32,
+        group: &Group,
+        asset: Option<Address>,
+        frozen: &Vec<Expense>,
+        settlements: &mut Vec<Settlement>,
+    ) {
+        let mut debtors: Vec<(Address, i128)> = Vec::new(env);
+        let mut creditors: Vec<(Address, i128)> = Vec::new(env);
 
         for member in group.members.iter() {
-            let bal: i128 = env
+            let mut bal: i128 = env
                 .storage()
                 .persistent()
-                .get(&DataKey::Balance(group_id, member.clone()))
+                .get(&Self::balance_key(group_id, member.clone(), &asset))
                 .unwrap_or(0);
+            for expense in frozen.iter() {
+                if expense.asset == asset {
+                    bal -= Self::expense_delta(&expense, &member);
+                }
+            }
             if bal < 0 {
                 debtors.push_back((member.clone(), -bal));
             } else if bal > 0 {
@@ -504,9 +3956,7 @@ impl LumenSplit {
             }
         }
 
-        let mut settlements: Vec<Settlement> = Vec::new(&env);
-
-        while (debtors.len() > 0) && (creditors.len() > 0) {
+        while !debtors.is_empty() && !creditors.is_empty() {
             let (d_addr, d_amt) = debtors.get(0).unwrap().clone();
             let (c_addr, c_amt) = creditors.get(0).unwrap().clone();
 
@@ -516,6 +3966,7 @@ impl LumenSplit {
                 from: d_addr.clone(),
                 to: c_addr.clone(),
                 amount: settle_amt,
+                asset: asset.clone(),
             });
 
             let new_d = d_amt - settle_amt;
@@ -533,107 +3984,98 @@ impl LumenSplit {
                 creditors.set(0, (c_addr, new_c));
             }
         }
-
-        Ok(settlements)
-    }
-
-    pub fn get_groups_for_member(env: Env, member: Address) -> Vec<u32> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::MemberGroups(member))
-            .unwrap_or(Vec::new(&env))
-    }
-
-    pub fn get_expenses(env: Env, group_id: u32) -> Vec<Expense> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Expenses(group_id))
-            .unwrap_or(Vec::new(&env))
     }
 
-    pub fn delete_group(env: Env, actor: Address, group_id: u32) -> Result<(), Error> {
-        actor.require_auth();
-
-        let group: Group = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Group(group_id))
-            .ok_or(Error::GroupNotFound)?;
-
-        if actor != group.creator {
-            return Err(Error::NotAuthorized);
-        }
-
-        for member in group.members.iter() {
-            let mut member_groups: Vec<u32> = env
-                .storage()
-                .persistent()
-                .get(&DataKey::MemberGroups(member.clone()))
-                .unwrap_or(Vec::new(&env));
-
-            let mut i = 0;
-            let mut found = false;
-            while i < member_groups.len() {
-                if member_groups.get(i).unwrap() == group_id {
-                    member_groups.remove(i);
-                    found = true;
-                    break;
-                }
-                i += 1;
-            }
-
-            if found {
-                env.storage()
-                    .persistent()
-                    .set(&DataKey::MemberGroups(member.clone()), &member_groups);
-            }
-            env.storage()
-                .persistent()
-                .remove(&DataKey::Balance(group_id, member));
-        }
-
-        env.storage().persistent().remove(&DataKey::Group(group_id));
-        env.storage()
-            .persistent()
-            .remove(&DataKey::Expenses(group_id));
-
-        env.events()
-            .publish((symbol!("group"), symbol!("deleted")), group_id);
-
-        Ok(())
+    fn record_activity(
+        env: &Env,
+        group_id: u32,
+        kind: ActivityType,
+        actor: Address,
+        recipient: Option<Address>,
+        amount: i128,
+        asset: Option<Address>,
+    ) {
+        Self::push_activity(
+            env,
+            group_id,
+            Activity {
+                id: 0,
+                kind,
+                actor,
+                recipient,
+                amount,
+                asset,
+                expense_id: None,
+                receipt_id: None,
+                item_index: None,
+                timestamp: env.ledger().timestamp(),
+            },
+        );
     }
 
-    pub fn get_activities(env: Env, group_id: u32) -> Vec<Activity> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Activities(group_id))
-            .unwrap_or(Vec::new(&env))
+    fn record_expense_activity(
+        env: &Env,
+        group_id: u32,
+        kind: ActivityType,
+        actor: Address,
+        amount: i128,
+        asset: Option<Address>,
+        expense_id: u32,
+    ) {
+        Self::push_activity(
+            env,
+            group_id,
+            Activity {
+                id: 0,
+                kind,
+                actor,
+                recipient: None,
+                amount,
+                asset,
+                expense_id: Some(expense_id),
+                receipt_id: None,
+                item_index: None,
+                timestamp: env.ledger().timestamp(),
+            },
+        );
     }
 
-    fn record_activity(
+    fn record_receipt_activity(
         env: &Env,
         group_id: u32,
         kind: ActivityType,
         actor: Address,
-        recipient: Option<Address>,
         amount: i128,
+        receipt_id: u32,
+        item_index: Option<u32>,
     ) {
+        Self::push_activity(
+            env,
+            group_id,
+            Activity {
+                id: 0,
+                kind,
+                actor,
+                recipient: None,
+                amount,
+                asset: None,
+                expense_id: None,
+                receipt_id: Some(receipt_id),
+                item_index,
+                timestamp: env.ledger().timestamp(),
+            },
+        );
+    }
+
+    fn push_activity(env: &Env, group_id: u32, mut activity: Activity) {
         let mut activities: Vec<Activity> = env
             .storage()
             .persistent()
             .get(&DataKey::Activities(group_id))
             .unwrap_or(Vec::new(env));
 
-        let id = activities.len() + 1;
-
-        activities.push_back(Activity {
-            id,
-            kind,
-            actor,
-            recipient,
-            amount,
-            timestamp: env.ledger().timestamp(),
-        });
+        activity.id = activities.len() + 1;
+        activities.push_back(activity);
 
         env.storage()
             .persistent()
@@ -1,12 +1,36 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{testutils::Address as _, vec, Address, Env};
+use soroban_sdk::{
+    contract, contractimpl,
+    testutils::{Address as _, Ledger, MockAuth, MockAuthInvoke},
+    token::{StellarAssetClient, TokenClient},
+    vec, Address, Env, IntoVal,
+};
+
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(env: Env, asset: Address, price: i128, timestamp: u64) {
+        env.storage()
+            .instance()
+            .set(&asset, &PriceData { price, timestamp });
+    }
+
+    pub fn lastprice(env: Env, asset: OracleAsset) -> Option<PriceData> {
+        match asset {
+            OracleAsset::Stellar(asset) => env.storage().instance().get(&asset),
+            OracleAsset::Other(_) => None,
+        }
+    }
+}
 
 #[test]
 fn test_create_group() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -23,7 +47,7 @@ fn test_create_group() {
     client.register(&member1, &String::from_str(&env, "Member 1"));
     client.register(&member2, &String::from_str(&env, "Member 2"));
 
-    let group_id = client.create_group(&creator, &group_name, &members);
+    let group_id = client.create_group(&creator, &group_name, &members, &None);
     assert_eq!(group_id, 1);
 
     let group = client.get_group(&group_id);
@@ -39,7 +63,7 @@ fn test_create_group() {
 #[test]
 fn test_add_expense_equal_split() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -55,7 +79,7 @@ fn test_add_expense_equal_split() {
     client.register(&member1, &String::from_str(&env, "Member 1"));
     client.register(&member2, &String::from_str(&env, "Member 2"));
 
-    let group_id = client.create_group(&creator, &String::from_str(&env, "Trip"), &members);
+    let group_id = client.create_group(&creator, &String::from_str(&env, "Trip"), &members, &None);
 
     let participants = vec![&env, creator.clone(), member1.clone(), member2.clone()];
     client.add_expense(&creator, &group_id, &300, &participants);
@@ -68,7 +92,7 @@ fn test_add_expense_equal_split() {
 #[test]
 fn test_settle_debt() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -84,6 +108,7 @@ fn test_settle_debt() {
         &creator,
         &String::from_str(&env, "Settlement Test"),
         &vec![&env, member1.clone()],
+        &None,
     );
 
     client.add_expense(
@@ -105,7 +130,7 @@ fn test_settle_debt() {
 #[test]
 fn test_add_expense_non_member() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -117,7 +142,12 @@ fn test_add_expense_non_member() {
     client.register(&creator, &String::from_str(&env, "Creator"));
     client.register(&non_member, &String::from_str(&env, "Non Member"));
 
-    let group_id = client.create_group(&creator, &String::from_str(&env, "Test"), &vec![&env]);
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Test"),
+        &vec![&env],
+        &None,
+    );
 
     let result = client.try_add_expense(&non_member, &group_id, &100, &vec![&env, creator.clone()]);
     assert_eq!(result, Err(Ok(Error::NotAMember)));
@@ -126,7 +156,7 @@ fn test_add_expense_non_member() {
 #[test]
 fn test_add_member() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -144,6 +174,7 @@ fn test_add_member() {
         &creator,
         &String::from_str(&env, "Add Member"),
         &vec![&env, member1.clone()],
+        &None,
     );
 
     client.add_member(&creator, &group_id, &member2);
@@ -159,7 +190,7 @@ fn test_add_member() {
 #[should_panic(expected = "HostError: Error(Contract, #3)")]
 fn test_settle_debt_safety_over_settle() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -174,6 +205,7 @@ fn test_settle_debt_safety_over_settle() {
         &creator,
         &String::from_str(&env, "Safety Test"),
         &vec![&env, member1.clone()],
+        &None,
     );
 
     client.add_expense(
@@ -191,7 +223,7 @@ fn test_settle_debt_safety_over_settle() {
 #[should_panic(expected = "HostError: Error(Contract, #3)")]
 fn test_settle_debt_safety_no_debt() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -206,6 +238,7 @@ fn test_settle_debt_safety_no_debt() {
         &creator,
         &String::from_str(&env, "Safety Test 2"),
         &vec![&env, member1.clone()],
+        &None,
     );
 
     // member1 has 0 balance. Trying to settle should fail.
@@ -216,7 +249,7 @@ fn test_settle_debt_safety_no_debt() {
 #[should_panic(expected = "HostError: Error(Contract, #6)")]
 fn test_create_group_unregistered_member() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -231,6 +264,7 @@ fn test_create_group_unregistered_member() {
         &creator,
         &String::from_str(&env, "Bad Group"),
         &vec![&env, member1.clone()],
+        &None,
     );
 }
 
@@ -238,7 +272,7 @@ fn test_create_group_unregistered_member() {
 #[should_panic(expected = "HostError: Error(Contract, #6)")]
 fn test_add_unregistered_member() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -248,8 +282,12 @@ fn test_add_unregistered_member() {
 
     client.register(&creator, &String::from_str(&env, "Creator"));
 
-    let group_id =
-        client.create_group(&creator, &String::from_str(&env, "Good Group"), &vec![&env]);
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Good Group"),
+        &vec![&env],
+        &None,
+    );
 
     // member1 is NOT registered
     client.add_member(&creator, &group_id, &member1);
@@ -258,7 +296,7 @@ fn test_add_unregistered_member() {
 #[test]
 fn test_activity_log() {
     let env = Env::default();
-    let contract_id = env.register_contract(None, LumenSplit);
+    let contract_id = env.register(LumenSplit, ());
     let client = LumenSplitClient::new(&env, &contract_id);
 
     let creator = Address::generate(&env);
@@ -274,6 +312,7 @@ fn test_activity_log() {
         &creator,
         &String::from_str(&env, "Activity Test"),
         &vec![&env, member1.clone()],
+        &None,
     );
 
     let activities = client.get_activities(&group_id);
@@ -322,3 +361,2813 @@ fn test_activity_log() {
     assert_eq!(a4.kind, ActivityType::MemberAdded);
     assert_eq!(a4.recipient, Some(member2));
 }
+
+#[test]
+fn test_settle_debt_transfers_settlement_token() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    let token = TokenClient::new(&env, &token_id);
+    StellarAssetClient::new(&env, &token_id).mint(&member1, &1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Token Settlement"),
+        &vec![&env, member1.clone()],
+        &Some(token_id.clone()),
+    );
+    assert_eq!(client.get_group(&group_id).settlement_token, Some(token_id));
+
+    client.add_expense(
+        &creator,
+        &group_id,
+        &100,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    client.settle_debt(&member1, &group_id, &creator, &50);
+
+    assert_eq!(client.get_balance(&group_id, &creator), 0);
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+    assert_eq!(token.balance(&member1), 950);
+    assert_eq!(token.balance(&creator), 50);
+}
+
+#[test]
+fn test_settle_debt_insufficient_funds_keeps_balances() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    StellarAssetClient::new(&env, &token_id).mint(&member1, &10);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Broke"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+    client.set_settlement_token(&creator, &group_id, &token_id);
+
+    client.add_expense(
+        &creator,
+        &group_id,
+        &100,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    // member1 only holds 10, so the transfer fails and the ledger must not move.
+    assert!(client
+        .try_settle_debt(&member1, &group_id, &creator, &50)
+        .is_err());
+    assert_eq!(client.get_balance(&group_id, &creator), 50);
+    assert_eq!(client.get_balance(&group_id, &member1), -50);
+}
+
+#[test]
+fn test_set_settlement_token_creator_only() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Token Admin"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+
+    let result = client.try_set_settlement_token(&member1, &group_id, &token_id);
+    assert_eq!(result, Err(Ok(Error::NotAuthorized)));
+}
+
+#[test]
+fn test_set_settlement_token_requires_settled_group() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let xlm = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let usdc = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "USDC Trip"),
+        &vec![&env, member1.clone()],
+        &Some(usdc.clone()),
+    );
+    assert_eq!(
+        client.get_group_with_balances(&group_id).settlement_token,
+        Some(usdc.clone())
+    );
+
+    client.add_expense(
+        &creator,
+        &group_id,
+        &100,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    // Open balances are in USDC; re-denominating them must be refused.
+    let result = client.try_set_settlement_token(&creator, &group_id, &xlm);
+    assert_eq!(result, Err(Ok(Error::OutstandingBalances)));
+    assert_eq!(client.get_group(&group_id).settlement_token, Some(usdc));
+}
+
+#[test]
+fn test_settlement_token_locked_while_pot_is_funded() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let victim = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let usdc = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let junk = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    StellarAssetClient::new(&env, &usdc).mint(&victim, &1_000);
+    StellarAssetClient::new(&env, &junk).mint(&creator, &1_000);
+    let usdc_client = TokenClient::new(&env, &usdc);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&victim, &String::from_str(&env, "Victim"));
+
+    let other_group = client.create_group(
+        &victim,
+        &String::from_str(&env, "Savings"),
+        &vec![&env, member1.clone()],
+        &Some(usdc.clone()),
+    );
+    client.deposit_to_pot(&victim, &other_group, &1_000);
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Junk"),
+        &vec![&env, member1.clone()],
+        &Some(junk.clone()),
+    );
+    client.deposit_to_pot(&creator, &group_id, &1_000);
+
+    // Switching to USDC would let the junk deposit be withdrawn as USDC.
+    assert_eq!(
+        client.try_set_settlement_token(&creator, &group_id, &usdc),
+        Err(Ok(Error::PotNotEmpty))
+    );
+    assert_eq!(client.get_group(&group_id).settlement_token, Some(junk));
+    assert_eq!(usdc_client.balance(&contract_id), 1_000);
+
+    client.withdraw_from_pot(&creator, &group_id, &1_000);
+    client.set_settlement_token(&creator, &group_id, &usdc);
+    assert_eq!(
+        client.try_withdraw_from_pot(&creator, &group_id, &1_000),
+        Err(Ok(Error::InsufficientPot))
+    );
+    assert_eq!(usdc_client.balance(&contract_id), 1_000);
+}
+
+#[test]
+fn test_multi_currency_expenses_keep_separate_ledgers() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let usd = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let eur = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Euro Trip"),
+        &vec![&env, member1.clone()],
+        &Some(usd.clone()),
+    );
+
+    let both = vec![&env, creator.clone(), member1.clone()];
+    // EUR dinner paid by the creator, USD taxi paid by member1.
+    client.add_asset_expense(&creator, &group_id, &eur, &80, &both);
+    client.add_asset_expense(&member1, &group_id, &usd, &30, &both);
+
+    assert_eq!(client.get_asset_balance(&group_id, &creator, &eur), 40);
+    assert_eq!(client.get_asset_balance(&group_id, &member1, &eur), -40);
+    // The settlement token is the group's main ledger.
+    assert_eq!(client.get_balance(&group_id, &creator), -15);
+    assert_eq!(client.get_balance(&group_id, &member1), 15);
+
+    let expenses = client.get_expenses(&group_id);
+    assert_eq!(expenses.get(0).unwrap().asset, Some(eur.clone()));
+    assert_eq!(expenses.get(1).unwrap().asset, None);
+
+    let group = client.get_group_with_balances(&group_id);
+    assert_eq!(group.members.get(0).unwrap().address, member1);
+    assert_eq!(group.members.get(0).unwrap().balance, 15);
+    assert_eq!(
+        group.members.get(0).unwrap().asset_balances,
+        vec![
+            &env,
+            AssetBalance {
+                asset: eur.clone(),
+                balance: -40,
+            }
+        ]
+    );
+
+    let settlements = client.get_settlements(&group_id);
+    assert_eq!(
+        settlements,
+        vec![
+            &env,
+            Settlement {
+                from: creator.clone(),
+                to: member1.clone(),
+                amount: 15,
+                asset: None,
+            },
+            Settlement {
+                from: member1.clone(),
+                to: creator.clone(),
+                amount: 40,
+                asset: Some(eur),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_settle_asset_debt_transfers_asset() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let eur = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    StellarAssetClient::new(&env, &eur).mint(&member1, &100);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Paris"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+
+    client.add_asset_expense(
+        &creator,
+        &group_id,
+        &eur,
+        &80,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    // Nothing is owed on the main ledger, only in EUR.
+    assert_eq!(
+        client.try_settle_debt(&member1, &group_id, &creator, &40),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    client.settle_asset_debt(&member1, &group_id, &creator, &eur, &40);
+
+    assert_eq!(client.get_asset_balance(&group_id, &member1, &eur), 0);
+    assert_eq!(client.get_asset_balance(&group_id, &creator, &eur), 0);
+    assert_eq!(TokenClient::new(&env, &eur).balance(&creator), 40);
+
+    let activities = client.get_activities(&group_id);
+    assert_eq!(activities.get(2).unwrap().kind, ActivityType::Settlement);
+    assert_eq!(activities.get(2).unwrap().asset, Some(eur));
+}
+
+#[test]
+fn test_foreign_expense_converted_with_oracle() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+    env.ledger().set_timestamp(1_000);
+
+    let usd = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let eur = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+
+    let oracle_id = env.register(MockOracle, ());
+    let oracle = MockOracleClient::new(&env, &oracle_id);
+    oracle.set_price(&usd, &10_000_000, &990);
+    oracle.set_price(&eur, &11_000_000, &995);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Base USD"),
+        &vec![&env, member1.clone()],
+        &Some(usd),
+    );
+    client.set_price_oracle(&creator, &group_id, &Some(oracle_id), &60);
+
+    client.add_asset_expense(
+        &creator,
+        &group_id,
+        &eur,
+        &1_000,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    // 1,000 EUR at 1.10 lands on the USD ledger as 1,100.
+    assert_eq!(client.get_balance(&group_id, &creator), 550);
+    assert_eq!(client.get_balance(&group_id, &member1), -550);
+    assert_eq!(client.get_asset_balance(&group_id, &member1, &eur), 0);
+    assert!(client.get_group(&group_id).assets.is_empty());
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.amount, 1_100);
+    assert_eq!(expense.asset, None);
+    assert_eq!(
+        expense.conversion,
+        Conversion::Oracle(OracleRate {
+            asset: eur,
+            amount: 1_000,
+            price: 11_000_000,
+            base_price: 10_000_000,
+            timestamp: 990,
+        })
+    );
+}
+
+#[test]
+fn test_foreign_expense_rejects_stale_price() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+    env.ledger().set_timestamp(1_000);
+
+    let usd = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let eur = env
+        .register_stellar_asset_contract_v2(token_admin.clone())
+        .address();
+    let gbp = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+
+    let oracle_id = env.register(MockOracle, ());
+    let oracle = MockOracleClient::new(&env, &oracle_id);
+    oracle.set_price(&usd, &10_000_000, &1_000);
+    oracle.set_price(&eur, &11_000_000, &900);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let no_token = client.create_group(
+        &creator,
+        &String::from_str(&env, "No Base"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+    assert_eq!(
+        client.try_set_price_oracle(&creator, &no_token, &Some(oracle_id.clone()), &60),
+        Err(Ok(Error::SettlementTokenNotSet))
+    );
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Base USD"),
+        &vec![&env, member1.clone()],
+        &Some(usd),
+    );
+    client.set_price_oracle(&creator, &group_id, &Some(oracle_id), &60);
+
+    let participants = vec![&env, creator.clone(), member1.clone()];
+    assert_eq!(
+        client.try_add_asset_expense(&creator, &group_id, &eur, &1_000, &participants),
+        Err(Ok(Error::StalePrice))
+    );
+    assert_eq!(
+        client.try_add_asset_expense(&creator, &group_id, &gbp, &1_000, &participants),
+        Err(Ok(Error::PriceUnavailable))
+    );
+    assert!(client.get_expenses(&group_id).is_empty());
+}
+
+#[test]
+fn test_settle_all_executes_plan() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    let token = TokenClient::new(&env, &token_id);
+    let minter = StellarAssetClient::new(&env, &token_id);
+    minter.mint(&member1, &1_000);
+    minter.mint(&member2, &1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Settle All"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &Some(token_id),
+    );
+
+    client.add_expense(
+        &creator,
+        &group_id,
+        &300,
+        &vec![&env, creator.clone(), member1.clone(), member2.clone()],
+    );
+
+    let plan = client.get_settlements(&group_id);
+    let executed = client.settle_all(&group_id);
+    assert_eq!(executed, plan);
+    assert_eq!(executed.len(), 2);
+
+    let auths = env.auths();
+    assert!(auths.iter().any(|(addr, _)| *addr == member1));
+    assert!(auths.iter().any(|(addr, _)| *addr == member2));
+    assert!(!auths.iter().any(|(addr, _)| *addr == creator));
+
+    assert_eq!(client.get_balance(&group_id, &creator), 0);
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+    assert_eq!(client.get_balance(&group_id, &member2), 0);
+    assert_eq!(token.balance(&creator), 200);
+    assert_eq!(token.balance(&member1), 900);
+    assert_eq!(token.balance(&member2), 900);
+    assert!(client.get_settlements(&group_id).is_empty());
+
+    let activities = client.get_activities(&group_id);
+    assert_eq!(activities.len(), 4);
+    assert_eq!(activities.get(2).unwrap().kind, ActivityType::Settlement);
+    assert_eq!(activities.get(2).unwrap().actor, member1);
+    assert_eq!(activities.get(3).unwrap().kind, ActivityType::Settlement);
+    assert_eq!(activities.get(3).unwrap().actor, member2);
+}
+
+#[test]
+fn test_group_pot_lifecycle() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    let token = TokenClient::new(&env, &token_id);
+    let minter = StellarAssetClient::new(&env, &token_id);
+    minter.mint(&creator, &1_000);
+    minter.mint(&member1, &1_000);
+    minter.mint(&member2, &1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Kitty"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &Some(token_id),
+    );
+
+    client.deposit_to_pot(&creator, &group_id, &300);
+    client.deposit_to_pot(&member1, &group_id, &300);
+    client.deposit_to_pot(&member2, &group_id, &100);
+    assert_eq!(token.balance(&contract_id), 700);
+
+    // Groceries for everyone come out of the kitty, reimbursing member1 once
+    // the others have approved.
+    client.add_pot_expense(
+        &member1,
+        &group_id,
+        &300,
+        &vec![&env, creator.clone(), member1.clone(), member2.clone()],
+    );
+    let pending = client.get_pending_expenses(&group_id).get(0).unwrap();
+    assert_eq!(pending.required, 2);
+    client.approve_expense(&creator, &group_id, &pending.id);
+    assert_eq!(token.balance(&member1), 700);
+    client.approve_expense(&member2, &group_id, &pending.id);
+    assert_eq!(token.balance(&member1), 1_000);
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+    assert!(client.get_expenses(&group_id).get(0).unwrap().from_pot);
+
+    let pot = client.get_pot(&group_id);
+    assert_eq!(pot.total, 400);
+    let member2_pot = pot.members.get(1).unwrap();
+    assert_eq!(member2_pot.address, member2);
+    assert_eq!(member2_pot.contributed, 100);
+    assert_eq!(member2_pot.spent, 100);
+    assert_eq!(member2_pot.remaining, 0);
+
+    // member2 has consumed their whole contribution.
+    assert_eq!(
+        client.try_withdraw_from_pot(&member2, &group_id, &1),
+        Err(Ok(Error::InsufficientPot))
+    );
+    assert_eq!(
+        client.try_add_pot_expense(&creator, &group_id, &401, &vec![&env, creator.clone()]),
+        Err(Ok(Error::InsufficientPot))
+    );
+
+    client.withdraw_from_pot(&creator, &group_id, &100);
+    assert_eq!(token.balance(&creator), 800);
+    assert_eq!(client.get_pot(&group_id).total, 300);
+
+    // Closing the group splits the remaining 300 by unspent contribution:
+    // creator 100, member1 200.
+    client.delete_group(&creator, &group_id);
+    assert_eq!(token.balance(&contract_id), 0);
+    assert_eq!(token.balance(&creator), 900);
+    assert_eq!(token.balance(&member1), 1_200);
+    assert_eq!(token.balance(&member2), 900);
+}
+
+#[test]
+fn test_cash_payment_confirmation_flow() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Cash"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    client.add_expense(
+        &creator,
+        &group_id,
+        &300,
+        &vec![&env, creator.clone(), member1.clone(), member2.clone()],
+    );
+
+    // A claimed payment stays pending and leaves balances alone.
+    let first = client.record_payment(&member1, &group_id, &creator, &None, &60);
+    let second = client.record_payment(&member2, &group_id, &creator, &None, &100);
+    assert_eq!((first, second), (1, 2));
+    assert_eq!(client.get_pending_payments(&group_id).len(), 2);
+    assert_eq!(client.get_balance(&group_id, &member1), -100);
+
+    // Only the named creditor can act on it.
+    assert_eq!(
+        client.try_confirm_payment(&member2, &group_id, &first),
+        Err(Ok(Error::NotAuthorized))
+    );
+
+    client.confirm_payment(&creator, &group_id, &first);
+    assert_eq!(client.get_balance(&group_id, &member1), -40);
+    assert_eq!(client.get_balance(&group_id, &creator), 140);
+
+    client.reject_payment(&creator, &group_id, &second);
+    assert_eq!(client.get_balance(&group_id, &member2), -100);
+    assert!(client.get_pending_payments(&group_id).is_empty());
+    assert_eq!(
+        client.try_confirm_payment(&creator, &group_id, &second),
+        Err(Ok(Error::PaymentNotFound))
+    );
+
+    // The creditor can also log a payment they received directly.
+    client.record_received_payment(&creator, &group_id, &member2, &None, &100);
+    assert_eq!(client.get_balance(&group_id, &member2), 0);
+    assert_eq!(client.get_balance(&group_id, &creator), 40);
+
+    let activities = client.get_activities(&group_id);
+    assert_eq!(activities.len(), 4);
+    assert_eq!(activities.get(3).unwrap().kind, ActivityType::Settlement);
+    assert_eq!(activities.get(3).unwrap().actor, member2);
+}
+
+#[test]
+fn test_settle_debt_without_token_requires_creditor() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "No Token"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+
+    client.add_expense(
+        &creator,
+        &group_id,
+        &100,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    client.settle_debt(&member1, &group_id, &creator, &50);
+
+    let auths = env.auths();
+    assert!(auths.iter().any(|(addr, _)| *addr == member1));
+    assert!(auths.iter().any(|(addr, _)| *addr == creator));
+}
+
+#[test]
+fn test_misdirected_settlements_rejected() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+    let member3 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let eur = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    StellarAssetClient::new(&env, &eur).mint(&member1, &1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+    client.register(&member3, &String::from_str(&env, "Member 3"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Misdirected"),
+        &vec![&env, member1.clone(), member2.clone(), member3.clone()],
+        &None,
+    );
+
+    // creator +100, member1 -50, member2 -50, member3 untouched.
+    client.add_expense(
+        &creator,
+        &group_id,
+        &150,
+        &vec![&env, creator.clone(), member1.clone(), member2.clone()],
+    );
+    client.add_asset_expense(
+        &creator,
+        &group_id,
+        &eur,
+        &100,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+
+    let invalid = Err(Ok(Error::InvalidCreditor));
+
+    // A member who is owed nothing.
+    assert_eq!(
+        client.try_settle_debt(&member1, &group_id, &member3, &10),
+        invalid
+    );
+    // Another debtor.
+    assert_eq!(
+        client.try_settle_debt(&member1, &group_id, &member2, &10),
+        invalid
+    );
+    // Themselves.
+    assert_eq!(
+        client.try_settle_debt(&member1, &group_id, &member1, &10),
+        invalid
+    );
+    // A creditor on a different ledger only.
+    assert_eq!(
+        client.try_settle_asset_debt(&member1, &group_id, &member2, &eur, &10),
+        invalid
+    );
+    // Cash flows are held to the same rules.
+    assert_eq!(
+        client.try_record_payment(&member1, &group_id, &member3, &None, &10),
+        Err(Ok(Error::InvalidCreditor))
+    );
+    assert_eq!(
+        client.try_record_received_payment(&member3, &group_id, &member1, &None, &10),
+        invalid
+    );
+
+    // Nothing moved.
+    assert_eq!(client.get_balance(&group_id, &member1), -50);
+    assert_eq!(client.get_balance(&group_id, &member2), -50);
+    assert_eq!(client.get_balance(&group_id, &member3), 0);
+    assert!(client.get_pending_payments(&group_id).is_empty());
+}
+
+#[test]
+fn test_settle_debt_cannot_overpay_creditor() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Overpay"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    // member1 owes 90 in total but creditors are owed 60 and 30.
+    client.add_expense(
+        &creator,
+        &group_id,
+        &120,
+        &vec![&env, creator.clone(), member1.clone()],
+    );
+    client.add_expense(
+        &member2,
+        &group_id,
+        &60,
+        &vec![&env, member1.clone(), member2.clone()],
+    );
+    assert_eq!(client.get_balance(&group_id, &member1), -90);
+    assert_eq!(client.get_balance(&group_id, &member2), 30);
+
+    // Paying member2 the full 90 would leave them owing 60.
+    assert_eq!(
+        client.try_settle_debt(&member1, &group_id, &member2, &90),
+        Err(Ok(Error::InvalidCreditor))
+    );
+
+    let pending = client.record_payment(&member1, &group_id, &member2, &None, &30);
+    client.settle_debt(&member1, &group_id, &member2, &30);
+    // The claim no longer fits once member2 has been paid.
+    assert_eq!(
+        client.try_confirm_payment(&member2, &group_id, &pending),
+        Err(Ok(Error::InvalidCreditor))
+    );
+
+    client.settle_debt(&member1, &group_id, &creator, &60);
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+    assert_eq!(client.get_balance(&group_id, &member2), 0);
+    assert_eq!(client.get_balance(&group_id, &creator), 0);
+}
+
+#[test]
+fn test_cross_group_netting() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    let token = TokenClient::new(&env, &token_id);
+    StellarAssetClient::new(&env, &token_id).mint(&alice, &1_000);
+
+    client.register(&alice, &String::from_str(&env, "Alice"));
+    client.register(&bob, &String::from_str(&env, "Bob"));
+    client.register(&carol, &String::from_str(&env, "Carol"));
+
+    let dinners = client.create_group(
+        &alice,
+        &String::from_str(&env, "Dinners"),
+        &vec![&env, bob.clone()],
+        &Some(token_id.clone()),
+    );
+    let flat = client.create_group(
+        &alice,
+        &String::from_str(&env, "Flat"),
+        &vec![&env, bob.clone(), carol.clone()],
+        &Some(token_id),
+    );
+
+    // Dinners: alice owes bob 30. Flat: bob owes alice 20, carol owes alice 20.
+    client.add_expense(&bob, &dinners, &60, &vec![&env, alice.clone(), bob.clone()]);
+    client.add_expense(
+        &alice,
+        &flat,
+        &60,
+        &vec![&env, alice.clone(), bob.clone(), carol.clone()],
+    );
+
+    assert_eq!(client.get_net_position(&bob, &alice), 10);
+    assert_eq!(client.get_net_position(&alice, &bob), -10);
+
+    // bob is the net creditor and cannot initiate.
+    assert_eq!(
+        client.try_settle_net(&bob, &alice),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    assert_eq!(client.settle_net(&alice, &bob), 10);
+    assert_eq!(token.balance(&alice), 990);
+    assert_eq!(token.balance(&bob), 10);
+
+    assert_eq!(client.get_balance(&dinners, &alice), 0);
+    assert_eq!(client.get_balance(&dinners, &bob), 0);
+    assert_eq!(client.get_balance(&flat, &alice), 20);
+    assert_eq!(client.get_balance(&flat, &bob), 0);
+    assert_eq!(client.get_balance(&flat, &carol), -20);
+    assert_eq!(client.get_net_position(&alice, &bob), 0);
+
+    let dinner_activity = client.get_activities(&dinners).last().unwrap();
+    assert_eq!(dinner_activity.kind, ActivityType::Settlement);
+    assert_eq!(dinner_activity.actor, alice);
+    assert_eq!(dinner_activity.amount, 30);
+    let flat_activity = client.get_activities(&flat).last().unwrap();
+    assert_eq!(flat_activity.actor, bob);
+    assert_eq!(flat_activity.recipient, Some(alice));
+    assert_eq!(flat_activity.amount, 20);
+}
+
+#[test]
+fn test_cross_group_netting_requires_one_token() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&alice, &String::from_str(&env, "Alice"));
+    client.register(&bob, &String::from_str(&env, "Bob"));
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+
+    let cash = client.create_group(
+        &alice,
+        &String::from_str(&env, "Cash"),
+        &vec![&env, bob.clone()],
+        &None,
+    );
+    let tokens = client.create_group(
+        &alice,
+        &String::from_str(&env, "Tokens"),
+        &vec![&env, bob.clone()],
+        &Some(token_id),
+    );
+
+    client.add_expense(&bob, &cash, &60, &vec![&env, alice.clone(), bob.clone()]);
+    client.add_expense(
+        &alice,
+        &tokens,
+        &40,
+        &vec![&env, alice.clone(), bob.clone()],
+    );
+
+    assert_eq!(
+        client.try_settle_net(&alice, &bob),
+        Err(Ok(Error::AssetMismatch))
+    );
+    assert_eq!(client.get_balance(&cash, &alice), -30);
+    assert_eq!(client.get_balance(&tokens, &alice), 20);
+}
+
+#[test]
+fn test_weighted_split() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Weighted"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    // 3:2:2 of 100 rounds down to 42/28/28; the two leftover units go to
+    // the first participants listed.
+    let split = Split::Weighted(vec![
+        &env,
+        (creator.clone(), 3),
+        (member1.clone(), 2),
+        (member2.clone(), 2),
+    ]);
+    client.add_split_expense(&creator, &group_id, &None, &100, &split);
+
+    assert_eq!(client.get_balance(&group_id, &creator), 57);
+    assert_eq!(client.get_balance(&group_id, &member1), -29);
+    assert_eq!(client.get_balance(&group_id, &member2), -28);
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.split, split);
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (creator.clone(), 43),
+            (member1.clone(), 29),
+            (member2.clone(), 28),
+        ]
+    );
+    assert_eq!(
+        expense.participants,
+        vec![&env, creator.clone(), member1.clone(), member2.clone()]
+    );
+
+    assert_eq!(
+        client.try_add_split_expense(
+            &creator,
+            &group_id,
+            &None,
+            &100,
+            &Split::Weighted(vec![&env, (creator.clone(), 1), (member1.clone(), 0)]),
+        ),
+        Err(Ok(Error::InvalidWeight))
+    );
+}
+
+#[test]
+fn test_payer_outside_split_is_credited() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Gift"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    client.add_split_expense(
+        &creator,
+        &group_id,
+        &None,
+        &90,
+        &Split::Weighted(vec![&env, (member1.clone(), 2), (member2.clone(), 1)]),
+    );
+
+    assert_eq!(client.get_balance(&group_id, &creator), 90);
+    assert_eq!(client.get_balance(&group_id, &member1), -60);
+    assert_eq!(client.get_balance(&group_id, &member2), -30);
+}
+
+#[test]
+fn test_exact_split() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Restaurant"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    let shares = vec![
+        &env,
+        (creator.clone(), 1_250),
+        (member1.clone(), 3_100),
+        (member2.clone(), 650),
+    ];
+    client.add_split_expense(
+        &creator,
+        &group_id,
+        &None,
+        &5_000,
+        &Split::Exact(shares.clone()),
+    );
+
+    assert_eq!(client.get_balance(&group_id, &creator), 3_750);
+    assert_eq!(client.get_balance(&group_id, &member1), -3_100);
+    assert_eq!(client.get_balance(&group_id, &member2), -650);
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.split, Split::Exact(shares.clone()));
+    assert_eq!(expense.shares, shares);
+
+    assert_eq!(
+        client.try_add_split_expense(&creator, &group_id, &None, &4_999, &Split::Exact(shares),),
+        Err(Ok(Error::SharesMismatch))
+    );
+    assert_eq!(
+        client.try_add_split_expense(
+            &creator,
+            &group_id,
+            &None,
+            &100,
+            &Split::Exact(vec![&env, (creator.clone(), 100), (member1.clone(), 0)]),
+        ),
+        Err(Ok(Error::InvalidShare))
+    );
+    assert_eq!(
+        client.try_add_split_expense(
+            &creator,
+            &group_id,
+            &None,
+            &100,
+            &Split::Exact(vec![&env, (creator.clone(), 150), (member1.clone(), -50)]),
+        ),
+        Err(Ok(Error::InvalidShare))
+    );
+    assert_eq!(client.get_expenses(&group_id).len(), 1);
+}
+
+#[test]
+fn test_percent_split() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Rent"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    // 45% / 33.33% / 21.67% of 1,001 is 450.45 / 333.63 / 216.91, rounded
+    // down to 450 / 333 / 216 with the two leftover units going to the
+    // first two participants.
+    let rooms = Split::Percent(vec![
+        &env,
+        (creator.clone(), 4_500),
+        (member1.clone(), 3_333),
+        (member2.clone(), 2_167),
+    ]);
+    client.add_split_expense(&creator, &group_id, &None, &1_001, &rooms);
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.split, rooms);
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (creator.clone(), 451),
+            (member1.clone(), 334),
+            (member2.clone(), 216),
+        ]
+    );
+    assert_eq!(client.get_balance(&group_id, &creator), 550);
+    assert_eq!(client.get_balance(&group_id, &member1), -334);
+    assert_eq!(client.get_balance(&group_id, &member2), -216);
+
+    assert_eq!(
+        client.try_add_split_expense(
+            &creator,
+            &group_id,
+            &None,
+            &100,
+            &Split::Percent(vec![
+                &env,
+                (creator.clone(), 5_000),
+                (member1.clone(), 4_999)
+            ]),
+        ),
+        Err(Ok(Error::InvalidPercentages))
+    );
+}
+
+#[test]
+fn test_itemized_receipt_split() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Dinner"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    let receipt = Receipt {
+        items: vec![
+            &env,
+            LineItem {
+                name: String::from_str(&env, "Pizza"),
+                amount: 3_000,
+                participants: vec![&env, creator.clone(), member1.clone(), member2.clone()],
+            },
+            LineItem {
+                name: String::from_str(&env, "Wine"),
+                amount: 2_000,
+                participants: vec![&env, creator.clone(), member1.clone()],
+            },
+            LineItem {
+                name: String::from_str(&env, "Salad"),
+                amount: 500,
+                participants: vec![&env, member2.clone()],
+            },
+        ],
+        tax: 550,
+        tip: 1_101,
+    };
+
+    // Subtotals 2,000 / 2,000 / 1,500; the 1,651 of tax and tip splits
+    // 600 / 600 / 450 with the leftover unit going to the creator.
+    client.add_split_expense(
+        &creator,
+        &group_id,
+        &None,
+        &7_151,
+        &Split::Itemized(receipt.clone()),
+    );
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.split, Split::Itemized(receipt.clone()));
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (creator.clone(), 2_601),
+            (member1.clone(), 2_600),
+            (member2.clone(), 1_950),
+        ]
+    );
+    assert_eq!(client.get_balance(&group_id, &creator), 4_550);
+    assert_eq!(client.get_balance(&group_id, &member1), -2_600);
+    assert_eq!(client.get_balance(&group_id, &member2), -1_950);
+
+    assert_eq!(
+        client.try_add_split_expense(
+            &creator,
+            &group_id,
+            &None,
+            &7_150,
+            &Split::Itemized(receipt),
+        ),
+        Err(Ok(Error::SharesMismatch))
+    );
+}
+
+#[test]
+fn test_finalize_receipt_respects_approval_threshold() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Receipts"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+    client.set_approval_policy(&creator, &group_id, &100, &0, &0);
+
+    let receipt_id = client.post_receipt(
+        &creator,
+        &group_id,
+        &vec![&env, (String::from_str(&env, "Television"), 10_000)],
+        &0,
+        &0,
+    );
+    assert_eq!(
+        client.try_finalize_receipt(&creator, &group_id, &receipt_id, &UnclaimedRule::AllMembers),
+        Err(Ok(Error::UnclaimedOverThreshold))
+    );
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+
+    // The payer can still keep what nobody claimed.
+    client.finalize_receipt(&creator, &group_id, &receipt_id, &UnclaimedRule::Payer);
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+
+    // Small leftovers are shared out as before.
+    let receipt_id = client.post_receipt(
+        &creator,
+        &group_id,
+        &vec![&env, (String::from_str(&env, "Snacks"), 150)],
+        &0,
+        &0,
+    );
+    client.finalize_receipt(&creator, &group_id, &receipt_id, &UnclaimedRule::AllMembers);
+    assert_eq!(client.get_balance(&group_id, &member1), -75);
+}
+
+#[test]
+fn test_claimable_receipt() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Claims"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    let receipt_id = client.post_receipt(
+        &creator,
+        &group_id,
+        &vec![
+            &env,
+            (String::from_str(&env, "Pizza"), 3_000),
+            (String::from_str(&env, "Wine"), 1_000),
+        ],
+        &400,
+        &0,
+    );
+    assert_eq!(receipt_id, 1);
+    assert_eq!(client.get_balance(&group_id, &creator), 0);
+
+    // Pizza plus its 300 slice of the tax.
+    client.claim_item(&member1, &group_id, &receipt_id, &0);
+    assert_eq!(client.get_balance(&group_id, &member1), -3_300);
+    assert_eq!(client.get_balance(&group_id, &creator), 3_300);
+    assert_eq!(
+        client.try_claim_item(&member1, &group_id, &receipt_id, &0),
+        Err(Ok(Error::AlreadyClaimed))
+    );
+
+    client.claim_item(&member2, &group_id, &receipt_id, &0);
+    assert_eq!(client.get_balance(&group_id, &member1), -1_650);
+    assert_eq!(client.get_balance(&group_id, &member2), -1_650);
+
+    client.claim_item(&member2, &group_id, &receipt_id, &1);
+    assert_eq!(client.get_balance(&group_id, &member2), -2_750);
+    assert_eq!(client.get_balance(&group_id, &creator), 4_400);
+
+    client.unclaim_item(&member2, &group_id, &receipt_id, &1);
+    assert_eq!(client.get_balance(&group_id, &member2), -1_650);
+    assert_eq!(
+        client.try_unclaim_item(&member2, &group_id, &receipt_id, &1),
+        Err(Ok(Error::NotClaimed))
+    );
+
+    assert_eq!(
+        client.try_finalize_receipt(&member1, &group_id, &receipt_id, &UnclaimedRule::Payer),
+        Err(Ok(Error::NotAuthorized))
+    );
+
+    // The unclaimed wine is shared by the whole group.
+    client.finalize_receipt(&creator, &group_id, &receipt_id, &UnclaimedRule::AllMembers);
+    assert_eq!(client.get_balance(&group_id, &member1), -2_017);
+    assert_eq!(client.get_balance(&group_id, &member2), -2_017);
+    assert_eq!(client.get_balance(&group_id, &creator), 4_034);
+
+    assert_eq!(
+        client.try_claim_item(&member1, &group_id, &receipt_id, &1),
+        Err(Ok(Error::ReceiptFinalized))
+    );
+    assert!(client.get_receipts(&group_id).get(0).unwrap().finalized);
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.amount, 4_400);
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (member1.clone(), 2_017),
+            (member2.clone(), 2_017),
+            (creator.clone(), 366),
+        ]
+    );
+
+    let activities = client.get_activities(&group_id);
+    assert_eq!(activities.get(1).unwrap().kind, ActivityType::ReceiptPosted);
+    let claimed = activities.get(2).unwrap();
+    assert_eq!(claimed.kind, ActivityType::ItemClaimed);
+    assert_eq!(claimed.amount, 3_300);
+    assert_eq!(claimed.receipt_id, Some(receipt_id));
+    assert_eq!(claimed.item_index, Some(0));
+    let unclaimed = activities.get(5).unwrap();
+    assert_eq!(unclaimed.kind, ActivityType::ItemUnclaimed);
+    assert_eq!(unclaimed.amount, -1_100);
+    assert_eq!(unclaimed.item_index, Some(1));
+    assert_eq!(
+        activities.last().unwrap().kind,
+        ActivityType::ReceiptFinalized
+    );
+}
+
+#[test]
+fn test_multi_payer_expense() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+    let outsider = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Two Cards"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+
+    let payers = vec![&env, (creator.clone(), 60), (member1.clone(), 40)];
+    client.add_multi_payer_expense(
+        &member2,
+        &group_id,
+        &None,
+        &payers,
+        &Split::Equal(vec![
+            &env,
+            creator.clone(),
+            member1.clone(),
+            member2.clone(),
+        ]),
+    );
+
+    let auths = env.auths();
+    assert!(auths.iter().any(|(addr, _)| *addr == creator));
+    assert!(auths.iter().any(|(addr, _)| *addr == member1));
+    assert!(auths.iter().any(|(addr, _)| *addr == member2));
+
+    assert_eq!(client.get_balance(&group_id, &creator), 26);
+    assert_eq!(client.get_balance(&group_id, &member1), 7);
+    assert_eq!(client.get_balance(&group_id, &member2), -33);
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.payer, member2);
+    assert_eq!(expense.payers, payers);
+    assert_eq!(expense.amount, 100);
+
+    let activities = client.get_activities(&group_id);
+    assert_eq!(activities.len(), 3);
+    assert_eq!(activities.get(1).unwrap().kind, ActivityType::Expense);
+    assert_eq!(activities.get(1).unwrap().actor, creator);
+    assert_eq!(activities.get(1).unwrap().amount, 60);
+    assert_eq!(activities.get(2).unwrap().actor, member1);
+    assert_eq!(activities.get(2).unwrap().amount, 40);
+
+    let everyone = Split::Equal(vec![&env, creator.clone(), member1.clone()]);
+    assert_eq!(
+        client.try_add_multi_payer_expense(
+            &creator,
+            &group_id,
+            &None,
+            &vec![&env, (creator.clone(), 50), (outsider, 50)],
+            &everyone,
+        ),
+        Err(Ok(Error::NotAMember))
+    );
+    assert_eq!(
+        client.try_add_multi_payer_expense(
+            &creator,
+            &group_id,
+            &None,
+            &vec![&env, (creator.clone(), 50), (member1.clone(), 0)],
+            &everyone,
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_remainder_policy() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Leftovers"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let everyone = Split::Equal(vec![
+        &env,
+        creator.clone(),
+        member1.clone(),
+        member2.clone(),
+    ]);
+
+    // By default the first listed participant takes the extra unit.
+    client.add_split_expense(&creator, &group_id, &None, &10, &everyone);
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.remainder_policy, RemainderPolicy::FirstListed);
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (creator.clone(), 4),
+            (member1.clone(), 3),
+            (member2.clone(), 3)
+        ]
+    );
+
+    assert_eq!(
+        client.try_set_remainder_policy(&member1, &group_id, &RemainderPolicy::Rotate),
+        Err(Ok(Error::NotAuthorized))
+    );
+
+    // Rotation moves the extra unit along with every uneven expense.
+    client.set_remainder_policy(&creator, &group_id, &RemainderPolicy::Rotate);
+    for (i, expected) in [
+        creator.clone(),
+        member1.clone(),
+        member2.clone(),
+        creator.clone(),
+    ]
+    .iter()
+    .enumerate()
+    {
+        client.add_split_expense(&creator, &group_id, &None, &10, &everyone);
+        let expense = client.get_expenses(&group_id).get(i as u32 + 1).unwrap();
+        assert_eq!(expense.remainder_policy, RemainderPolicy::Rotate);
+        assert_eq!(expense.remainder_start, i as u32 % 3);
+        for (participant, share) in expense.shares.iter() {
+            assert_eq!(share, if participant == *expected { 4 } else { 3 });
+        }
+    }
+
+    // The payer covers the leftover even when they are not in the split.
+    client.set_remainder_policy(&creator, &group_id, &RemainderPolicy::PayerAbsorbs);
+    let before = client.get_balance(&group_id, &member1);
+    client.add_split_expense(
+        &member1,
+        &group_id,
+        &None,
+        &11,
+        &Split::Equal(vec![&env, creator.clone(), member2.clone()]),
+    );
+    let expense = client.get_expenses(&group_id).get(5).unwrap();
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (creator.clone(), 5),
+            (member2.clone(), 5),
+            (member1.clone(), 1)
+        ]
+    );
+    assert_eq!(client.get_balance(&group_id, &member1), before + 10);
+
+    // Rounding inside itemized receipts follows the policy too.
+    let receipt = Receipt {
+        items: vec![
+            &env,
+            LineItem {
+                name: String::from_str(&env, "Chips"),
+                amount: 11,
+                participants: vec![&env, creator.clone(), member2.clone()],
+            },
+        ],
+        tax: 0,
+        tip: 0,
+    };
+    client.add_split_expense(&member1, &group_id, &None, &11, &Split::Itemized(receipt));
+    let expense = client.get_expenses(&group_id).get(6).unwrap();
+    assert_eq!(expense.remainder_policy, RemainderPolicy::PayerAbsorbs);
+    assert_eq!(
+        expense.shares,
+        vec![
+            &env,
+            (creator.clone(), 5),
+            (member2.clone(), 5),
+            (member1.clone(), 1)
+        ]
+    );
+
+    client.set_remainder_policy(&creator, &group_id, &RemainderPolicy::Random);
+    client.add_split_expense(&creator, &group_id, &None, &10, &everyone);
+    let expense = client.get_expenses(&group_id).get(7).unwrap();
+    assert!(expense.remainder_start < 3);
+    let (_, lucky) = expense.shares.get(expense.remainder_start).unwrap();
+    assert_eq!(lucky, 4);
+
+    let mut total = 0;
+    for member in [creator, member1, member2] {
+        total += client.get_balance(&group_id, &member);
+    }
+    assert_eq!(total, 0);
+}
+
+#[test]
+fn test_edit_needs_payers_losing_credit() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Shared"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let split = Split::Equal(vec![&env, member1.clone(), member2.clone()]);
+    client.add_multi_payer_expense(
+        &member1,
+        &group_id,
+        &None,
+        &vec![&env, (member1.clone(), 50), (member2.clone(), 50)],
+        &split,
+    );
+
+    // member1 alone cannot move member2's credit to themselves.
+    let payers = vec![&env, (member1.clone(), 100)];
+    env.mock_auths(&[MockAuth {
+        address: &member1,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "edit_expense",
+            args: (
+                member1.clone(),
+                group_id,
+                1u32,
+                payers.clone(),
+                split.clone(),
+            )
+                .into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client
+        .try_edit_expense(&member1, &group_id, &1, &payers, &split)
+        .is_err());
+    assert_eq!(client.get_balance(&group_id, &member2), 0);
+
+    env.mock_all_auths();
+    client.edit_expense(&member1, &group_id, &1, &payers, &split);
+    assert!(env.auths().iter().any(|(a, _)| *a == member2));
+    assert_eq!(client.get_balance(&group_id, &member1), 50);
+    assert_eq!(client.get_balance(&group_id, &member2), -50);
+
+    // The group creator can correct a payer's credit on their own.
+    let payers = vec![&env, (member1.clone(), 60)];
+    env.mock_auths(&[MockAuth {
+        address: &creator,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "edit_expense",
+            args: (
+                creator.clone(),
+                group_id,
+                1u32,
+                payers.clone(),
+                split.clone(),
+            )
+                .into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.edit_expense(&creator, &group_id, &1, &payers, &split);
+    assert_eq!(client.get_balance(&group_id, &member1), 30);
+    assert_eq!(client.get_balance(&group_id, &member2), -30);
+}
+
+#[test]
+fn test_edit_and_void_expense() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Typos"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let everyone = Split::Equal(vec![
+        &env,
+        creator.clone(),
+        member1.clone(),
+        member2.clone(),
+    ]);
+
+    // member1 meant to enter 30, not 300.
+    client.add_split_expense(&member1, &group_id, &None, &300, &everyone);
+    client.add_split_expense(&member2, &group_id, &None, &60, &everyone);
+
+    assert_eq!(
+        client.try_edit_expense(
+            &member2,
+            &group_id,
+            &1,
+            &vec![&env, (member1.clone(), 30)],
+            &everyone
+        ),
+        Err(Ok(Error::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_void_expense(&member1, &group_id, &5),
+        Err(Ok(Error::ExpenseNotFound))
+    );
+
+    client.edit_expense(
+        &member1,
+        &group_id,
+        &1,
+        &vec![&env, (member1.clone(), 30)],
+        &everyone,
+    );
+    assert_eq!(client.get_balance(&group_id, &creator), -30);
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+    assert_eq!(client.get_balance(&group_id, &member2), 30);
+
+    let expense = client.get_expenses(&group_id).get(0).unwrap();
+    assert_eq!(expense.amount, 30);
+    assert_eq!(expense.revisions.len(), 1);
+    let revision = expense.revisions.get(0).unwrap();
+    assert_eq!(revision.editor, member1);
+    assert_eq!(revision.amount, 300);
+
+    // The creator can void someone else's expense.
+    client.void_expense(&creator, &group_id, &2);
+    assert_eq!(client.get_balance(&group_id, &creator), -10);
+    assert_eq!(client.get_balance(&group_id, &member1), 20);
+    assert_eq!(client.get_balance(&group_id, &member2), -10);
+    assert!(client.get_expenses(&group_id).get(1).unwrap().voided);
+
+    assert_eq!(
+        client.try_edit_expense(
+            &member2,
+            &group_id,
+            &2,
+            &vec![&env, (member2.clone(), 90)],
+            &everyone
+        ),
+        Err(Ok(Error::ExpenseVoided))
+    );
+
+    let activities = client.get_activities(&group_id);
+    let edited = activities.get(activities.len() - 2).unwrap();
+    assert_eq!(edited.kind, ActivityType::ExpenseEdited);
+    assert_eq!(edited.actor, member1);
+    assert_eq!(edited.amount, 30);
+    let voided = activities.get(activities.len() - 1).unwrap();
+    assert_eq!(voided.kind, ActivityType::ExpenseVoided);
+    assert_eq!(voided.actor, creator);
+}
+
+#[test]
+fn test_expense_ids() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Ledger"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+    let both = vec![&env, creator.clone(), member1.clone()];
+
+    client.add_expense(&creator, &group_id, &100, &both);
+    client.add_expense(&member1, &group_id, &40, &both);
+    client.void_expense(&creator, &group_id, &1);
+
+    let expense = client.get_expense(&group_id, &2);
+    assert_eq!(expense.id, 2);
+    assert_eq!(expense.payer, member1);
+    assert_eq!(expense.amount, 40);
+    assert_eq!(client.get_expense(&group_id, &1).id, 1);
+    assert_eq!(
+        client.try_get_expense(&group_id, &0),
+        Err(Ok(Error::ExpenseNotFound))
+    );
+    assert_eq!(
+        client.try_get_expense(&group_id, &3),
+        Err(Ok(Error::ExpenseNotFound))
+    );
+
+    let activities = client.get_activities(&group_id);
+    assert_eq!(activities.get(0).unwrap().expense_id, None);
+    assert_eq!(activities.get(1).unwrap().expense_id, Some(1));
+    assert_eq!(activities.get(2).unwrap().expense_id, Some(2));
+    assert_eq!(activities.get(3).unwrap().kind, ActivityType::ExpenseVoided);
+    assert_eq!(activities.get(3).unwrap().expense_id, Some(1));
+}
+
+#[test]
+fn test_expense_details() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+
+    env.mock_all_auths();
+    env.ledger().set_timestamp(100_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Trip"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+    let payers = vec![&env, (creator.clone(), 150)];
+    let split = Split::Equal(vec![&env, creator.clone(), member1.clone()]);
+
+    // Yesterday's dinner, entered today.
+    let details = ExpenseDetails {
+        title: String::from_str(&env, "Dinner at the harbour"),
+        note: Some(String::from_str(&env, "Includes the wine")),
+        category: String::from_str(&env, "food"),
+        occurred_at: 100_000 - 86_400,
+        receipt_hash: None,
+        receipt_uri: None,
+    };
+    client.add_detailed_expense(&creator, &group_id, &None, &payers, &split, &details);
+
+    let expense = client.get_expense(&group_id, &1);
+    assert_eq!(expense.details, details);
+    assert_eq!(expense.timestamp, 100_000);
+    assert_eq!(client.get_balance(&group_id, &member1), -75);
+
+    // Expenses added without details get an empty title dated now.
+    client.add_expense(&member1, &group_id, &10, &vec![&env, creator.clone()]);
+    let plain = client.get_expense(&group_id, &2).details;
+    assert_eq!(plain.title, String::from_str(&env, ""));
+    assert_eq!(plain.note, None);
+    assert_eq!(plain.occurred_at, 100_000);
+
+    let mut invalid = details.clone();
+    invalid.title = String::from_bytes(&env, &[b'x'; 65]);
+    assert_eq!(
+        client.try_add_detailed_expense(&creator, &group_id, &None, &payers, &split, &invalid),
+        Err(Ok(Error::TitleTooLong))
+    );
+
+    let mut invalid = details.clone();
+    invalid.note = Some(String::from_bytes(&env, &[b'x'; 257]));
+    assert_eq!(
+        client.try_add_detailed_expense(&creator, &group_id, &None, &payers, &split, &invalid),
+        Err(Ok(Error::NoteTooLong))
+    );
+
+    let mut invalid = details.clone();
+    invalid.category = String::from_bytes(&env, &[b'x'; 33]);
+    assert_eq!(
+        client.try_add_detailed_expense(&creator, &group_id, &None, &payers, &split, &invalid),
+        Err(Ok(Error::CategoryTooLong))
+    );
+
+    let mut invalid = details.clone();
+    invalid.occurred_at = 100_001;
+    assert_eq!(
+        client.try_add_detailed_expense(&creator, &group_id, &None, &payers, &split, &invalid),
+        Err(Ok(Error::InvalidDate))
+    );
+
+    assert_eq!(client.get_expenses(&group_id).len(), 2);
+}
+
+#[test]
+fn test_category_summary() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+    env.ledger().set_timestamp(5_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Holiday"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let everyone = Split::Equal(vec![
+        &env,
+        creator.clone(),
+        member1.clone(),
+        member2.clone(),
+    ]);
+    let details = |category: &str, occurred_at: u64| ExpenseDetails {
+        title: String::from_str(&env, ""),
+        note: None,
+        category: String::from_str(&env, category),
+        occurred_at,
+        receipt_hash: None,
+        receipt_uri: None,
+    };
+
+    client.add_detailed_expense(
+        &creator,
+        &group_id,
+        &None,
+        &vec![&env, (creator.clone(), 90)],
+        &everyone,
+        &details("food", 1_000),
+    );
+    client.add_detailed_expense(
+        &member1,
+        &group_id,
+        &None,
+        &vec![&env, (member1.clone(), 30)],
+        &everyone,
+        &details("transport", 2_000),
+    );
+    client.add_detailed_expense(
+        &member2,
+        &group_id,
+        &None,
+        &vec![&env, (member2.clone(), 10)],
+        &everyone,
+        &details("food", 3_000),
+    );
+    client.add_detailed_expense(
+        &member2,
+        &group_id,
+        &None,
+        &vec![&env, (member2.clone(), 500)],
+        &everyone,
+        &details("food", 3_000),
+    );
+    client.void_expense(&member2, &group_id, &4);
+
+    let summaries = client.get_category_summary(&group_id, &None, &None);
+    assert_eq!(summaries.len(), 2);
+
+    // The uneven 10 puts the extra unit on the first participant, exactly
+    // as it was charged.
+    let food = summaries.get(0).unwrap();
+    assert_eq!(food.category, String::from_str(&env, "food"));
+    assert_eq!(food.asset, None);
+    assert_eq!(food.total, 100);
+    assert_eq!(
+        food.members,
+        vec![
+            &env,
+            CategoryMember {
+                address: creator.clone(),
+                paid: 90,
+                consumed: 34,
+            },
+            CategoryMember {
+                address: member1.clone(),
+                paid: 0,
+                consumed: 33,
+            },
+            CategoryMember {
+                address: member2.clone(),
+                paid: 10,
+                consumed: 33,
+            },
+        ]
+    );
+
+    let transport = summaries.get(1).unwrap();
+    assert_eq!(transport.total, 30);
+    let payer = transport.members.get(0).unwrap();
+    assert_eq!(payer.address, member1);
+    assert_eq!(payer.paid, 30);
+    assert_eq!(payer.consumed, 10);
+
+    let later = client.get_category_summary(&group_id, &Some(1_500), &Some(2_500));
+    assert_eq!(later.len(), 1);
+    assert_eq!(
+        later.get(0).unwrap().category,
+        String::from_str(&env, "transport")
+    );
+
+    assert_eq!(
+        client.try_get_category_summary(&99, &None, &None),
+        Err(Ok(Error::GroupNotFound))
+    );
+}
+
+#[test]
+fn test_recurring_expenses() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+    env.ledger().set_timestamp(1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Flat"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let everyone = Split::Equal(vec![
+        &env,
+        creator.clone(),
+        member1.clone(),
+        member2.clone(),
+    ]);
+    let rent = ExpenseDetails {
+        title: String::from_str(&env, "Rent"),
+        note: None,
+        category: String::from_str(&env, "housing"),
+        occurred_at: 0,
+        receipt_hash: None,
+        receipt_uri: None,
+    };
+
+    let rent_id = client.add_recurring_expense(
+        &member1,
+        &group_id,
+        &300,
+        &everyone,
+        &rent,
+        &Recurrence {
+            interval: 100,
+            start: 1_000,
+            end: None,
+            count: Some(3),
+        },
+    );
+    let streaming_id = client.add_recurring_expense(
+        &member2,
+        &group_id,
+        &30,
+        &everyone,
+        &rent,
+        &Recurrence {
+            interval: 100,
+            start: 1_050,
+            end: Some(1_400),
+            count: None,
+        },
+    );
+    assert_eq!(
+        client.try_add_recurring_expense(
+            &member2,
+            &group_id,
+            &30,
+            &everyone,
+            &rent,
+            &Recurrence {
+                interval: 0,
+                start: 1_050,
+                end: None,
+                count: None,
+            },
+        ),
+        Err(Ok(Error::InvalidSchedule))
+    );
+
+    assert_eq!(client.process_due(&group_id), 1);
+    assert_eq!(client.process_due(&group_id), 0);
+    assert_eq!(client.get_balance(&group_id, &member1), 200);
+
+    // Nobody called in for a while, so the missed periods are caught up.
+    env.ledger().set_timestamp(1_260);
+    assert_eq!(client.process_due(&group_id), 5);
+
+    let expenses = client.get_expenses(&group_id);
+    assert_eq!(expenses.len(), 6);
+    let mut rent_dates = Vec::new(&env);
+    for expense in expenses.iter() {
+        if expense.payer == member1 {
+            assert_eq!(expense.details.title, String::from_str(&env, "Rent"));
+            rent_dates.push_back(expense.details.occurred_at);
+        }
+    }
+    assert_eq!(rent_dates, vec![&env, 1_000, 1_100, 1_200]);
+    assert_eq!(client.get_balance(&group_id, &member1), 600 - 30);
+    assert_eq!(client.get_balance(&group_id, &member2), -300 + 60);
+
+    // The rent stopped after three instances.
+    let templates = client.get_recurring_expenses(&group_id);
+    assert!(!templates.get(0).unwrap().active);
+    assert_eq!(templates.get(0).unwrap().posted, 3);
+    assert!(templates.get(1).unwrap().active);
+
+    assert_eq!(
+        client.try_cancel_recurring_expense(&member1, &group_id, &streaming_id),
+        Err(Ok(Error::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_cancel_recurring_expense(&member1, &group_id, &7),
+        Err(Ok(Error::RecurringNotFound))
+    );
+    client.cancel_recurring_expense(&creator, &group_id, &streaming_id);
+
+    env.ledger().set_timestamp(5_000);
+    assert_eq!(client.process_due(&group_id), 0);
+    assert_eq!(client.get_expenses(&group_id).len(), 6);
+    assert_eq!(rent_id, 1);
+}
+
+#[test]
+fn test_expense_approval() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+    env.ledger().set_timestamp(1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Careful"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let everyone = vec![&env, creator.clone(), member1.clone(), member2.clone()];
+
+    assert_eq!(
+        client.try_set_approval_policy(&member1, &group_id, &100, &0, &0),
+        Err(Ok(Error::NotAuthorized))
+    );
+    client.set_approval_policy(&creator, &group_id, &100, &0, &0);
+
+    // Small expenses still apply straight away.
+    client.add_expense(&creator, &group_id, &60, &everyone);
+    assert_eq!(client.get_balance(&group_id, &creator), 40);
+
+    client.add_expense(&creator, &group_id, &300, &everyone);
+    assert_eq!(client.get_balance(&group_id, &creator), 40);
+    assert_eq!(client.get_expenses(&group_id).len(), 1);
+
+    let pending = client.get_pending_expenses(&group_id);
+    assert_eq!(pending.len(), 1);
+    let entry = pending.get(0).unwrap();
+    assert_eq!(
+        entry.approvers,
+        vec![&env, member1.clone(), member2.clone()]
+    );
+    assert_eq!(entry.required, 2);
+    assert_eq!(entry.expires_at, 0);
+
+    assert_eq!(
+        client.try_approve_expense(&creator, &group_id, &entry.id),
+        Err(Ok(Error::NotAuthorized))
+    );
+    client.approve_expense(&member1, &group_id, &entry.id);
+    assert_eq!(
+        client.try_approve_expense(&member1, &group_id, &entry.id),
+        Err(Ok(Error::AlreadyApproved))
+    );
+    assert_eq!(client.get_balance(&group_id, &member1), -20);
+
+    client.approve_expense(&member2, &group_id, &entry.id);
+    assert!(client.get_pending_expenses(&group_id).is_empty());
+    assert_eq!(client.get_balance(&group_id, &creator), 240);
+    assert_eq!(client.get_balance(&group_id, &member1), -120);
+    assert_eq!(client.get_expense(&group_id, &2).amount, 300);
+
+    // One approval is enough now, within a 100 second window.
+    client.set_approval_policy(&creator, &group_id, &100, &1, &100);
+
+    client.add_expense(&member1, &group_id, &600, &everyone);
+    client.reject_expense(&member2, &group_id, &2);
+    assert!(client.get_pending_expenses(&group_id).is_empty());
+    assert_eq!(
+        client.try_approve_expense(&creator, &group_id, &2),
+        Err(Ok(Error::PendingExpenseNotFound))
+    );
+
+    client.add_expense(&member1, &group_id, &600, &everyone);
+    let entry = client.get_pending_expenses(&group_id).get(0).unwrap();
+    assert_eq!(entry.required, 1);
+    assert_eq!(entry.expires_at, 1_100);
+
+    env.ledger().set_timestamp(1_101);
+    assert_eq!(
+        client.try_approve_expense(&creator, &group_id, &entry.id),
+        Err(Ok(Error::PendingExpired))
+    );
+    assert_eq!(client.expire_pending_expenses(&group_id), 1);
+    assert!(client.get_pending_expenses(&group_id).is_empty());
+
+    assert_eq!(client.get_expenses(&group_id).len(), 2);
+    assert_eq!(client.get_balance(&group_id, &member1), -120);
+
+    // Editing the small expense past the threshold needs approval too.
+    client.edit_expense(
+        &creator,
+        &group_id,
+        &1,
+        &vec![&env, (creator.clone(), 600)],
+        &Split::Equal(everyone.clone()),
+    );
+    assert_eq!(client.get_balance(&group_id, &creator), 240);
+    assert_eq!(client.get_expense(&group_id, &1).amount, 60);
+    let entry = client.get_pending_expenses(&group_id).get(0).unwrap();
+    assert_eq!(entry.replaces, Some(1));
+    assert_eq!(entry.editor, Some(creator.clone()));
+
+    client.approve_expense(&member2, &group_id, &entry.id);
+    assert!(client.get_pending_expenses(&group_id).is_empty());
+    let expense = client.get_expense(&group_id, &1);
+    assert_eq!(expense.id, 1);
+    assert_eq!(expense.amount, 600);
+    assert_eq!(expense.revisions.len(), 1);
+    assert_eq!(client.get_expenses(&group_id).len(), 2);
+    assert_eq!(client.get_balance(&group_id, &creator), 600);
+    assert_eq!(client.get_balance(&group_id, &member1), -300);
+}
+
+#[test]
+fn test_expense_disputes() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Arguments"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let everyone = vec![&env, creator.clone(), member1.clone(), member2.clone()];
+
+    client.add_expense(&creator, &group_id, &300, &everyone);
+    client.add_expense(&member1, &group_id, &60, &everyone);
+
+    let reason = String::from_str(&env, "I was not at this dinner");
+    assert_eq!(
+        client.try_open_dispute(
+            &member1,
+            &group_id,
+            &1,
+            &String::from_bytes(&env, &[b'x'; 257])
+        ),
+        Err(Ok(Error::ReasonTooLong))
+    );
+    client.open_dispute(&member1, &group_id, &1, &reason);
+    assert_eq!(
+        client.try_open_dispute(&member2, &group_id, &1, &reason),
+        Err(Ok(Error::DisputeAlreadyOpen))
+    );
+
+    // The disputed expense is left out of the plan but not the balances.
+    assert_eq!(client.get_balance(&group_id, &creator), 180);
+    let settlements = client.get_settlements(&group_id);
+    assert_eq!(
+        settlements,
+        vec![
+            &env,
+            Settlement {
+                from: member2.clone(),
+                to: member1.clone(),
+                amount: 20,
+                asset: None,
+            },
+            Settlement {
+                from: creator.clone(),
+                to: member1.clone(),
+                amount: 20,
+                asset: None,
+            },
+        ]
+    );
+
+    client.vote_dispute(&member1, &group_id, &1, &true);
+    assert_eq!(
+        client.try_vote_dispute(&member1, &group_id, &1, &true),
+        Err(Ok(Error::AlreadyVoted))
+    );
+    assert!(!client.get_expense(&group_id, &1).voided);
+    client.vote_dispute(&member2, &group_id, &1, &true);
+
+    let dispute = client.get_disputes(&group_id).get(0).unwrap();
+    assert_eq!(dispute.status, DisputeStatus::Upheld);
+    assert_eq!(dispute.reason, reason);
+    assert!(client.get_expense(&group_id, &1).voided);
+    assert_eq!(client.get_balance(&group_id, &creator), -20);
+    assert_eq!(client.get_balance(&group_id, &member1), 40);
+
+    // A majority can also dismiss a dispute, leaving the expense in place.
+    client.add_expense(&member2, &group_id, &90, &everyone);
+    client.open_dispute(&creator, &group_id, &3, &reason);
+    client.vote_dispute(&member1, &group_id, &3, &false);
+    assert_eq!(
+        client.get_disputes(&group_id).get(1).unwrap().status,
+        DisputeStatus::Open
+    );
+    client.vote_dispute(&member2, &group_id, &3, &false);
+    assert_eq!(
+        client.get_disputes(&group_id).get(1).unwrap().status,
+        DisputeStatus::Dismissed
+    );
+    assert!(!client.get_expense(&group_id, &3).voided);
+    assert_eq!(
+        client.try_vote_dispute(&creator, &group_id, &3, &true),
+        Err(Ok(Error::DisputeNotFound))
+    );
+
+    // The payer can settle a dispute by amending the expense.
+    client.open_dispute(&member2, &group_id, &2, &reason);
+    client.edit_expense(
+        &member1,
+        &group_id,
+        &2,
+        &vec![&env, (member1.clone(), 30)],
+        &Split::Equal(everyone.clone()),
+    );
+    assert_eq!(
+        client.get_disputes(&group_id).get(2).unwrap().status,
+        DisputeStatus::Amended
+    );
+
+    let activities = client.get_activities(&group_id);
+    for kind in [
+        ActivityType::DisputeOpened,
+        ActivityType::DisputeUpheld,
+        ActivityType::DisputeDismissed,
+        ActivityType::DisputeResolved,
+    ] {
+        assert!(activities.iter().any(|a| a.kind == kind));
+    }
+}
+
+#[test]
+fn test_dispute_needs_more_than_the_opener() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Two"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+
+    // member1 is the only participant, so on their own they would be a
+    // majority of the participants.
+    client.add_expense(&creator, &group_id, &50, &vec![&env, member1.clone()]);
+    let reason = String::from_str(&env, "Not mine");
+    client.open_dispute(&member1, &group_id, &1, &reason);
+    assert!(client
+        .get_disputes(&group_id)
+        .get(0)
+        .unwrap()
+        .upheld_by
+        .is_empty());
+
+    client.vote_dispute(&member1, &group_id, &1, &true);
+    assert_eq!(
+        client.get_disputes(&group_id).get(0).unwrap().status,
+        DisputeStatus::Open
+    );
+    assert!(!client.get_expense(&group_id, &1).voided);
+    assert_eq!(client.get_balance(&group_id, &member1), -50);
+
+    // The payer has a vote as well.
+    client.vote_dispute(&creator, &group_id, &1, &true);
+    assert_eq!(
+        client.get_disputes(&group_id).get(0).unwrap().status,
+        DisputeStatus::Upheld
+    );
+    assert_eq!(client.get_balance(&group_id, &member1), 0);
+
+    // A split vote between the two of them leaves the expense standing.
+    client.add_expense(&creator, &group_id, &50, &vec![&env, member1.clone()]);
+    client.open_dispute(&member1, &group_id, &2, &reason);
+    client.vote_dispute(&member1, &group_id, &2, &true);
+    client.vote_dispute(&creator, &group_id, &2, &false);
+    assert_eq!(
+        client.get_disputes(&group_id).get(1).unwrap().status,
+        DisputeStatus::Dismissed
+    );
+    assert!(!client.get_expense(&group_id, &2).voided);
+
+    // Nor can the dismissed dispute be reopened to freeze the expense again.
+    assert_eq!(
+        client.try_open_dispute(&member1, &group_id, &2, &reason),
+        Err(Ok(Error::DisputeDismissed))
+    );
+    assert_eq!(client.get_settlements(&group_id).len(), 1);
+}
+
+#[test]
+fn test_receipt_attachment() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Receipts"),
+        &vec![&env, member1.clone()],
+        &None,
+    );
+    let both = vec![&env, creator.clone(), member1.clone()];
+    client.add_expense(&creator, &group_id, &80, &both);
+
+    let hash = BytesN::from_array(&env, &[7; 32]);
+    let uri = String::from_str(
+        &env,
+        "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+    );
+    assert_eq!(
+        client.try_attach_receipt(&member1, &group_id, &1, &hash, &uri),
+        Err(Ok(Error::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_attach_receipt(
+            &creator,
+            &group_id,
+            &1,
+            &hash,
+            &String::from_bytes(&env, &[b'x'; 129])
+        ),
+        Err(Ok(Error::UriTooLong))
+    );
+    assert!(!client.verify_receipt(&group_id, &1, &hash));
+
+    client.attach_receipt(&creator, &group_id, &1, &hash, &uri);
+    let expense = client.get_expense(&group_id, &1);
+    assert_eq!(expense.details.receipt_hash, Some(hash.clone()));
+    assert_eq!(expense.details.receipt_uri, Some(uri.clone()));
+    assert!(client.verify_receipt(&group_id, &1, &hash));
+    assert!(!client.verify_receipt(&group_id, &1, &BytesN::from_array(&env, &[8; 32])));
+
+    // A replacement receipt overrides the first, and survives an edit.
+    let replacement = BytesN::from_array(&env, &[9; 32]);
+    client.attach_receipt(&creator, &group_id, &1, &replacement, &uri);
+    client.edit_expense(
+        &creator,
+        &group_id,
+        &1,
+        &vec![&env, (creator.clone(), 90)],
+        &Split::Equal(both),
+    );
+    assert!(client.verify_receipt(&group_id, &1, &replacement));
+    assert!(!client.verify_receipt(&group_id, &1, &hash));
+
+    // The receipt can also come with the expense itself.
+    let details = ExpenseDetails {
+        title: String::from_str(&env, "Groceries"),
+        note: None,
+        category: String::from_str(&env, "food"),
+        occurred_at: 0,
+        receipt_hash: Some(hash.clone()),
+        receipt_uri: Some(uri.clone()),
+    };
+    let payers = vec![&env, (member1.clone(), 40)];
+    client.add_detailed_expense(
+        &member1,
+        &group_id,
+        &None,
+        &payers,
+        &Split::Equal(vec![&env, creator.clone(), member1.clone()]),
+        &details,
+    );
+    assert_eq!(client.get_expense(&group_id, &2).details, details);
+    assert!(client.verify_receipt(&group_id, &2, &hash));
+}
+
+#[test]
+fn test_income_entries() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let member2 = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+    client.register(&member2, &String::from_str(&env, "Member 2"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Flights"),
+        &vec![&env, member1.clone(), member2.clone()],
+        &None,
+    );
+    let travel = ExpenseDetails {
+        title: String::from_str(&env, "Airline refund"),
+        note: None,
+        category: String::from_str(&env, "travel"),
+        occurred_at: 0,
+        receipt_hash: None,
+        receipt_uri: None,
+    };
+
+    client.add_detailed_expense(
+        &creator,
+        &group_id,
+        &None,
+        &vec![&env, (creator.clone(), 300)],
+        &Split::Equal(vec![
+            &env,
+            creator.clone(),
+            member1.clone(),
+            member2.clone(),
+        ]),
+        &travel,
+    );
+
+    // member1 received the refund and owes it back by weight.
+    client.add_income(
+        &member1,
+        &group_id,
+        &None,
+        &120,
+        &Split::Weighted(vec![
+            &env,
+            (creator.clone(), 2),
+            (member1.clone(), 1),
+            (member2.clone(), 1),
+        ]),
+        &travel,
+    );
+    assert_eq!(client.get_balance(&group_id, &creator), 200 + 60);
+    assert_eq!(client.get_balance(&group_id, &member1), -100 - 120 + 30);
+    assert_eq!(client.get_balance(&group_id, &member2), -100 + 30);
+
+    let income = client.get_expense(&group_id, &2);
+    assert_eq!(income.kind, EntryKind::Income);
+    assert_eq!(income.payer, member1);
+    assert_eq!(client.get_expense(&group_id, &1).kind, EntryKind::Expense);
+
+    let activity = client.get_activities(&group_id).last().unwrap();
+    assert_eq!(activity.kind, ActivityType::Income);
+    assert_eq!(activity.expense_id, Some(2));
+
+    // Income is not spending.
+    let summaries = client.get_category_summary(&group_id, &None, &None);
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries.get(0).unwrap().total, 300);
+
+    assert_eq!(
+        client.try_add_income(
+            &member1,
+            &group_id,
+            &None,
+            &0,
+            &Split::Equal(vec![&env, creator.clone()]),
+            &travel
+        ),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    client.void_expense(&member1, &group_id, &2);
+    assert_eq!(client.get_balance(&group_id, &creator), 200);
+    assert_eq!(client.get_balance(&group_id, &member1), -100);
+    assert_eq!(client.get_balance(&group_id, &member2), -100);
+
+    // When the payer absorbs rounding, a receiver hands the odd unit on
+    // rather than keeping it.
+    client.set_remainder_policy(&creator, &group_id, &RemainderPolicy::PayerAbsorbs);
+    client.add_income(
+        &member1,
+        &group_id,
+        &None,
+        &100,
+        &Split::Equal(vec![
+            &env,
+            creator.clone(),
+            member1.clone(),
+            member2.clone(),
+        ]),
+        &travel,
+    );
+    assert_eq!(
+        client.get_expense(&group_id, &3).shares,
+        vec![
+            &env,
+            (creator.clone(), 34),
+            (member1.clone(), 33),
+            (member2.clone(), 33),
+        ]
+    );
+    assert_eq!(client.get_balance(&group_id, &creator), 200 + 34);
+    assert_eq!(client.get_balance(&group_id, &member1), -100 - 100 + 33);
+    assert_eq!(client.get_balance(&group_id, &member2), -100 + 33);
+}
+
+#[test]
+fn test_pot_expense_cannot_drain_others() {
+    let env = Env::default();
+    let contract_id = env.register(LumenSplit, ());
+    let client = LumenSplitClient::new(&env, &contract_id);
+
+    let creator = Address::generate(&env);
+    let member1 = Address::generate(&env);
+    let token_admin = Address::generate(&env);
+
+    env.mock_all_auths();
+
+    let token_id = env
+        .register_stellar_asset_contract_v2(token_admin)
+        .address();
+    let token = TokenClient::new(&env, &token_id);
+    StellarAssetClient::new(&env, &token_id).mint(&creator, &1_000);
+
+    client.register(&creator, &String::from_str(&env, "Creator"));
+    client.register(&member1, &String::from_str(&env, "Member 1"));
+
+    let group_id = client.create_group(
+        &creator,
+        &String::from_str(&env, "Kitty"),
+        &vec![&env, member1.clone()],
+        &Some(token_id),
+    );
+    client.deposit_to_pot(&creator, &group_id, &1_000);
+
+    // member1 put nothing in and tries to bill the creator's contribution.
+    client.add_pot_expense(&member1, &group_id, &1_000, &vec![&env, creator.clone()]);
+    assert_eq!(token.balance(&member1), 0);
+    assert_eq!(client.get_pot(&group_id).total, 1_000);
+
+    let pending = client.get_pending_expenses(&group_id).get(0).unwrap();
+    assert_eq!(pending.approvers, vec![&env, creator.clone()]);
+    client.reject_expense(&creator, &group_id, &pending.id);
+
+    // Nor can they charge the kitty to themselves.
+    assert_eq!(
+        client.try_add_pot_expense(&member1, &group_id, &1_000, &vec![&env, member1.clone()]),
+        Err(Ok(Error::InsufficientPot))
+    );
+
+    assert_eq!(token.balance(&member1), 0);
+    assert_eq!(token.balance(&contract_id), 1_000);
+    assert!(client.get_expenses(&group_id).is_empty());
+}
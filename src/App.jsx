@@ -40,6 +40,7 @@ const toI128 = (v) =>
 const toString = (v) => nativeToScVal(v, { type: "string" });
 const toAddressVec = (arr) =>
   xdr.ScVal.scvVec(arr.map((a) => new Address(a).toScVal()));
+const toNone = () => xdr.ScVal.scvVoid();
 
 const STROOPS_PER_XLM = 10000000n;
 const formatAmount = (v) => (Number(v || 0) / 10000000).toFixed(2);
@@ -87,6 +88,7 @@ export default function App() {
   const [selectedGroupId, setSelectedGroupId] = useState("");
   const [selectedGroup, setSelectedGroup] = useState(null);
   const [settlements, setSettlements] = useState([]);
+  const [pendingPayments, setPendingPayments] = useState([]);
   const [expenseHistory, setExpenseHistory] = useState([]);
   const [activities, setActivities] = useState([]);
 
@@ -109,6 +111,7 @@ export default function App() {
     setSelectedGroupId("");
     setSelectedGroup(null);
     setSettlements([]);
+    setPendingPayments([]);
     setExpenseHistory([]);
     setBillAmount("");
     setSettleAmount("");
@@ -239,11 +242,12 @@ export default function App() {
         setSelectedGroup(null);
         return;
       }
-      const [g, settsRaw, expsRaw, actsRaw] = await Promise.all([
+      const [g, settsRaw, expsRaw, actsRaw, paysRaw] = await Promise.all([
         callRead("get_group_with_balances", [toU32(groupId)]),
         callRead("get_settlements", [toU32(groupId)]),
         callRead("get_expenses", [toU32(groupId)]),
         callRead("get_activities", [toU32(groupId)]),
+        callRead("get_pending_payments", [toU32(groupId)]),
       ]);
 
       if (!g) {
@@ -278,6 +282,18 @@ export default function App() {
         })),
       );
 
+      const pays = paysRaw || [];
+      setPendingPayments(
+        pays.map((p) => ({
+          id: Number(p.id?.toString ? p.id.toString() : p.id || 0),
+          from: p.from?.toString ? p.from.toString() : "",
+          to: p.to?.toString ? p.to.toString() : "",
+          amount: Number(
+            p.amount?.toString ? p.amount.toString() : p.amount || 0,
+          ),
+        })),
+      );
+
       const exps = expsRaw || [];
       setExpenseHistory(
         exps
@@ -876,6 +892,7 @@ export default function App() {
                               new Address(publicKey).toScVal(),
                               toString(name),
                               toAddressVec(members),
+                              toNone(),
                             ],
                             "Group created",
                           );
@@ -1170,10 +1187,46 @@ export default function App() {
                           <button className="button" onClick={async () => {
                           if (!settleTo) return updateStatus("Recipient required", "error");
                           if (!settleAmount || Number(settleAmount) <= 0) return updateStatus("Invalid amount", "error");
-                          await runWrite("settle_debt", [new Address(publicKey).toScVal(), toU32(selectedGroupId), new Address(settleTo).toScVal(), toI128(settleAmount)], "Bill settled");
+                          // Groups without a settlement token settle off-chain, so the
+                          // payment is recorded here and confirmed by the recipient.
+                          await runWrite("record_payment", [new Address(publicKey).toScVal(), toU32(selectedGroupId), new Address(settleTo).toScVal(), toNone(), toI128(settleAmount)], "Payment recorded");
                           setSettleAmount("");
                           }} disabled={isBusy}>Settle Bill</button>
 
+                        {pendingPayments.length > 0 && (
+                          <div
+                            style={{
+                              width: "100%",
+                              marginTop: "1rem",
+                              padding: "0.75rem",
+                              background: "rgba(255,255,255,0.05)",
+                              borderRadius: "8px",
+                              textAlign: "left",
+                            }}
+                          >
+                            <h4>Awaiting Confirmation</h4>
+                            {pendingPayments.map((p) => (
+                              <div key={p.id} style={{ padding: "0.35rem 0" }}>
+                                {selectedGroup.members.find(
+                                  (m) => m.address === p.from,
+                                )?.name || short(p.from)}{" "}
+                                paid{" "}
+                                <strong>{formatAmount(p.amount)} XLM</strong> to{" "}
+                                {selectedGroup.members.find(
+                                  (m) => m.address === p.to,
+                                )?.name || short(p.to)}
+                                {p.to === publicKey && (
+                                  <>
+                                    {" "}
+                                    <button className="button" onClick={() => runWrite("confirm_payment", [new Address(publicKey).toScVal(), toU32(selectedGroupId), toU32(p.id)], "Payment confirmed").catch(() => {})} disabled={isBusy}>Confirm</button>
+                                    <button className="button" onClick={() => runWrite("reject_payment", [new Address(publicKey).toScVal(), toU32(selectedGroupId), toU32(p.id)], "Payment rejected").catch(() => {})} disabled={isBusy}>Reject</button>
+                                  </>
+                                )}
+                              </div>
+                            ))}
+                          </div>
+                        )}
+
                         {settlements.length > 0 && (
                           <div
                             style={{